@@ -1,12 +1,144 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use hangar::{garmin, resource_path};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+/// The number of times the rows of the sample are repeated in the large log, about 20 hours at one row per second
+const LARGE_LOG_COPIES: usize = 20;
+
+/// A long log, made of the rows of the sample repeated, written once to the temporary directory
+fn large_log() -> PathBuf {
+    let path = std::env::temp_dir().join("hangar_bench_large_log.csv");
+    if path.exists() {
+        return path;
+    }
+    let sample = std::fs::read(resource_path(SAMPLE_CSV)).unwrap();
+    let sample = &sample[..sample.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)];
+    let lines = sample.split_inclusive(|&b| b == b'\n').collect::<Vec<_>>();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+    lines[..3].iter().for_each(|line| file.write_all(line).unwrap());
+    for _ in 0..LARGE_LOG_COPIES {
+        lines[3..].iter().for_each(|line| file.write_all(line).unwrap());
+    }
+    file.flush().unwrap();
+    path
+}
+
+/// The reader before logs were read in batches: the header read, then the whole file read into memory, verified
+/// into a second copy and parsed at once
+mod two_pass {
+    use hangar::data::clean_dataframe;
+    use hangar::garmin::{log_check, GarminEISLogHeader};
+    use polars::prelude::*;
+    use std::io::Read;
+    use std::path::Path;
+
+    const HEADER_LINES: usize = 3;
+
+    fn read_bytes(path: &Path) -> std::io::Result<Vec<u8>> {
+        let mut file = std::fs::File::open(path)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        while buffer.last() == Some(&0) {
+            buffer.pop();
+        }
+        Ok(buffer)
+    }
+
+    fn verify_line(line: &[u8]) -> bool {
+        let start = line.iter().rposition(|&b| b == b',').map_or(0, |i| i + 1);
+        let padding = line[start..].iter().take_while(|b| b.is_ascii_whitespace()).count();
+        let (checked, value) = line.split_at(start + padding);
+        let recorded = std::str::from_utf8(value)
+            .ok()
+            .and_then(|v| u16::from_str_radix(v.trim(), 16).ok());
+        recorded == Some(log_check(checked))
+    }
+
+    /// Drop the blank lines and the lines that fail their LogCheck
+    fn verify(buffer: Vec<u8>) -> Vec<u8> {
+        let mut verified = Vec::with_capacity(buffer.len());
+        for (i, line) in buffer.split_inclusive(|&b| b == b'\n').enumerate() {
+            let content = line.trim_ascii_end();
+            if i >= HEADER_LINES && (content.is_empty() || !verify_line(content)) {
+                continue;
+            }
+            verified.extend_from_slice(line);
+        }
+        verified
+    }
+
+    fn parse_datetime(lazy: LazyFrame) -> LazyFrame {
+        lazy.with_column(
+            concat_str(
+                vec![
+                    col("Lcl Date").dt().strftime("%Y-%m-%d"),
+                    lit("T"),
+                    col("Lcl Time"),
+                    col("UTCOfst"),
+                ],
+                "",
+                false,
+            )
+            .str()
+            .to_datetime(
+                Some(TimeUnit::Microseconds),
+                Some("UTC".into()),
+                StrptimeOptions {
+                    format: Some("%Y-%m-%dT%H:%M:%S%z".into()),
+                    ..Default::default()
+                },
+                lit("raise"),
+            )
+            .alias("Timestamp"),
+        )
+        .drop(vec!["Lcl Date", "Lcl Time", "UTCOfst"])
+    }
+
+    pub fn read(path: &Path) -> PolarsResult<DataFrame> {
+        let header = GarminEISLogHeader::from_csv(path)?;
+        let buffer = verify(read_bytes(path)?);
+        let data = CsvReadOptions::default()
+            .with_has_header(true)
+            .with_schema(Some(Arc::new(header.build_schema())))
+            .with_skip_rows(HEADER_LINES - 1)
+            .into_reader_with_file_handle(std::io::Cursor::new(buffer))
+            .finish()?;
+        clean_dataframe(parse_datetime(data.lazy()).collect()?)
+    }
+}
+
+fn read_two_pass(path: &Path) -> usize {
+    two_pass::read(path).unwrap().height()
+}
+
+fn read_whole(path: &Path) -> usize {
+    garmin::GarminEISLog::from_csv(path).unwrap().data.height()
+}
+
+/// Reading batches of rows and dropping each, as a batch job does, holds only one batch in memory at a time
+fn read_batches(path: &Path) -> usize {
+    garmin::GarminEISLogReader::open(path, garmin::LogCheckAction::Drop)
+        .unwrap()
+        .map(|batch| batch.unwrap().height())
+        .sum()
+}
 
 pub fn read_csv_eager(c: &mut Criterion) {
     let p = resource_path(SAMPLE_CSV);
     c.bench_function("read_csv", |b| b.iter(|| garmin::GarminEISLog::from_csv(&p).unwrap()));
 }
 
-criterion_group!(benches, read_csv_eager);
+pub fn read_large_log(c: &mut Criterion) {
+    let path = large_log();
+    let mut group = c.benchmark_group("read_large_log");
+    group.sample_size(10);
+    group.bench_function("two_pass", |b| b.iter(|| read_two_pass(&path)));
+    group.bench_function("whole", |b| b.iter(|| read_whole(&path)));
+    group.bench_function("batches", |b| b.iter(|| read_batches(&path)));
+    group.finish();
+}
+
+criterion_group!(benches, read_csv_eager, read_large_log);
 criterion_main!(benches);
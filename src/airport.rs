@@ -0,0 +1,311 @@
+//! Airports and runways, to identify where a flight took off and landed
+//!
+//! Airports are read from CSV files in the layout of the OurAirports data: airports.csv, and optionally runways.csv.
+//! The files may be cut down to the airports of a region. Heliports, seaplane bases and closed airports are skipped.
+//!
+//! The airport of a takeoff or landing is the one nearest the position at liftoff or touchdown, within a radius. The
+//! runway is the runway end whose true heading is closest to the direction of the ground roll, which is found from
+//! the positions so that it does not depend on whether a source records a magnetic or true heading. Where the
+//! aircraft did not move far enough on the ground, the heading (HDG) is used, corrected by the magnetic variation
+//! (MagVar) where it is recorded, as in the Garmin EIS log.
+
+use crate::analysis::phase::FlightPhases;
+use crate::data::{column_f64, timestamps};
+use crate::fdr::{CommentField, FDRField};
+use crate::geo::{positions, Position};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// One end of a runway, from which the runway is used
+#[derive(Debug, Clone, Serialize)]
+pub struct RunwayEnd {
+    /// The runway designator, such as "24" or "04L"
+    pub ident: String,
+    /// deg true
+    pub heading: f64,
+    /// The position of the threshold, where it is known
+    pub threshold: Option<Position>,
+    /// ft
+    pub length: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Airport {
+    /// The identifier, such as "KPOU"
+    pub ident: String,
+    pub name: String,
+    pub position: Position,
+    /// ft
+    pub elevation: Option<f64>,
+    pub runways: Vec<RunwayEnd>,
+}
+
+/// The airports of a region, or of the world
+#[derive(Debug, Clone, Default)]
+pub struct AirportDatabase {
+    pub airports: Vec<Airport>,
+}
+
+/// The text values of a column, read with every column as text
+fn column_str(df: &DataFrame, name: &str) -> Vec<Option<String>> {
+    match df.column(name).and_then(|c| c.str().cloned()) {
+        Ok(values) => values
+            .into_iter()
+            .map(|v| v.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string))
+            .collect(),
+        Err(_) => vec![None; df.height()],
+    }
+}
+
+/// Read a CSV file with every column as text
+fn read_csv(path: &Path) -> PolarsResult<DataFrame> {
+    CsvReadOptions::default()
+        .with_has_header(true)
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(path.into()))?
+        .finish()
+}
+
+impl AirportDatabase {
+    /// The types of airport that flights are identified at
+    const TYPES: [&str; 3] = ["large_airport", "medium_airport", "small_airport"];
+
+    /// Read the airports, and the runways if a runways file is given
+    pub fn from_csv(airports: &Path, runways: Option<&Path>) -> PolarsResult<Self> {
+        let df = read_csv(airports)?;
+        for name in ["ident", "latitude_deg", "longitude_deg"] {
+            df.column(name)?;
+        }
+        let ident = column_str(&df, "ident");
+        let kind = column_str(&df, "type");
+        let name = column_str(&df, "name");
+        let latitude = column_f64(&df, "latitude_deg");
+        let longitude = column_f64(&df, "longitude_deg");
+        let elevation = column_f64(&df, "elevation_ft");
+
+        let mut runways_of = match runways {
+            Some(path) => Self::read_runways(path)?,
+            None => HashMap::new(),
+        };
+        let airports = (0..df.height())
+            .filter(|&row| kind[row].as_deref().is_none_or(|kind| Self::TYPES.contains(&kind)))
+            .filter_map(|row| {
+                let ident = ident[row].clone()?;
+                Some(Airport {
+                    name: name[row].clone().unwrap_or_default(),
+                    position: Position::new(latitude[row]?, longitude[row]?),
+                    elevation: elevation[row],
+                    runways: runways_of.remove(&ident).unwrap_or_default(),
+                    ident,
+                })
+            })
+            .collect();
+        Ok(Self { airports })
+    }
+
+    /// Read the ends of the open runways of each airport, by the identifier of the airport
+    fn read_runways(path: &Path) -> PolarsResult<HashMap<String, Vec<RunwayEnd>>> {
+        let df = read_csv(path)?;
+        df.column("airport_ident")?;
+        let airport = column_str(&df, "airport_ident");
+        let closed = column_str(&df, "closed");
+        let length = column_f64(&df, "length_ft");
+        // the low numbered (le) and high numbered (he) ends of each runway
+        let end = |prefix: &str| {
+            let ident = column_str(&df, &format!("{}_ident", prefix));
+            let latitude = column_f64(&df, &format!("{}_latitude_deg", prefix));
+            let longitude = column_f64(&df, &format!("{}_longitude_deg", prefix));
+            let heading = column_f64(&df, &format!("{}_heading_degT", prefix));
+            (0..df.height())
+                .map(|row| {
+                    let threshold = latitude[row]
+                        .zip(longitude[row])
+                        .map(|(lat, lon)| Position::new(lat, lon));
+                    (ident[row].clone(), heading[row], threshold)
+                })
+                .collect::<Vec<_>>()
+        };
+        let (low, high) = (end("le"), end("he"));
+
+        let mut runways: HashMap<String, Vec<RunwayEnd>> = HashMap::new();
+        for row in 0..df.height() {
+            let Some(airport) = airport[row].clone() else {
+                continue;
+            };
+            if closed[row].as_deref() == Some("1") {
+                continue;
+            }
+            let (ends, opposites) = ([&low[row], &high[row]], [&high[row], &low[row]]);
+            for ((ident, heading, threshold), (_, _, opposite)) in ends.into_iter().zip(opposites) {
+                // without a recorded heading, the heading is that from this threshold to the other
+                let heading = heading.or_else(|| Some(threshold.as_ref()?.bearing_deg(opposite.as_ref()?)));
+                if let (Some(ident), Some(heading)) = (ident, heading) {
+                    runways.entry(airport.clone()).or_default().push(RunwayEnd {
+                        ident: ident.clone(),
+                        heading,
+                        threshold: *threshold,
+                        length: length[row],
+                    });
+                }
+            }
+        }
+        Ok(runways)
+    }
+
+    /// The airport nearest a position, if any is within the given distance
+    pub fn nearest(&self, position: &Position, max_distance_nm: f64) -> Option<(&Airport, f64)> {
+        self.airports
+            .iter()
+            .map(|airport| (airport, airport.position.distance_nm(position)))
+            .filter(|(_, distance)| *distance <= max_distance_nm)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Whether the aircraft took off or landed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AirportEvent {
+    Takeoff,
+    Landing,
+}
+
+/// A takeoff or landing at an identified airport
+#[derive(Debug, Clone, Serialize)]
+pub struct AirportVisit {
+    pub event: AirportEvent,
+    /// The row of liftoff or touchdown
+    pub row: usize,
+    pub time: Option<DateTime<Utc>>,
+    /// The identifier of the airport
+    pub airport: String,
+    pub name: String,
+    /// nm from the reference point of the airport
+    pub distance: f64,
+    /// The runway designator, where a runway lines up with the ground roll
+    pub runway: Option<String>,
+}
+
+impl fmt::Display for AirportVisit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.event {
+            AirportEvent::Takeoff => write!(f, "Takeoff from {}", self.airport)?,
+            AirportEvent::Landing => write!(f, "Landing at {}", self.airport)?,
+        }
+        if !self.name.is_empty() {
+            write!(f, " {}", self.name)?;
+        }
+        if let Some(runway) = &self.runway {
+            write!(f, ", runway {}", runway)?;
+        }
+        if let Some(time) = self.time {
+            write!(f, " at {}", time.format("%H:%M:%S"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies the airport and runway of each takeoff and landing
+#[derive(Debug, Clone)]
+pub struct AirportLocator {
+    /// nm from the reference point of an airport within which a takeoff or landing is at that airport
+    pub radius: f64,
+    /// deg, the largest difference between the direction of the ground roll and a runway heading
+    pub max_runway_deviation: f64,
+    /// rows of the ground roll before liftoff and after touchdown over which its direction is measured
+    pub roll_rows: usize,
+    /// nm the aircraft moves in the ground roll, below which its direction is taken from the heading
+    pub min_roll_distance: f64,
+}
+
+impl Default for AirportLocator {
+    fn default() -> Self {
+        Self {
+            radius: 3.0,
+            max_runway_deviation: 30.0,
+            roll_rows: 10,
+            min_roll_distance: 0.02,
+        }
+    }
+}
+
+impl AirportLocator {
+    /// The takeoffs and landings at airports of the database, in order
+    pub fn locate(&self, airports: &AirportDatabase, df: &DataFrame, phases: &FlightPhases) -> Vec<AirportVisit> {
+        let positions = positions(df);
+        let timestamps = timestamps(df);
+        let heading = column_f64(df, "HDG");
+        let variation = column_f64(df, "MagVar");
+        let n = df.height();
+
+        let mut events = phases
+            .takeoffs()
+            .into_iter()
+            .map(|row| (AirportEvent::Takeoff, row, row.saturating_sub(self.roll_rows), row))
+            .chain(
+                phases
+                    .landings()
+                    .into_iter()
+                    .map(|row| (AirportEvent::Landing, row, row, (row + self.roll_rows).min(n - 1))),
+            )
+            .collect::<Vec<_>>();
+        events.sort_by_key(|&(_, row, _, _)| row);
+
+        events
+            .into_iter()
+            .filter_map(|(event, row, roll_start, roll_end)| {
+                let position = positions[row]?;
+                let (airport, distance) = airports.nearest(&position, self.radius)?;
+                let roll = match (positions[roll_start], positions[roll_end]) {
+                    (Some(from), Some(to)) if from.distance_nm(&to) >= self.min_roll_distance => {
+                        Some(from.bearing_deg(&to))
+                    }
+                    _ => heading[row].map(|heading| heading + variation[row].unwrap_or(0.0)),
+                };
+                Some(AirportVisit {
+                    event,
+                    row,
+                    time: timestamps[row],
+                    airport: airport.ident.clone(),
+                    name: airport.name.clone(),
+                    distance,
+                    runway: roll.and_then(|roll| self.runway(airport, &position, roll)),
+                })
+            })
+            .collect()
+    }
+
+    /// The runway end lined up with the direction of the ground roll, the nearest to the position of parallel ones
+    fn runway(&self, airport: &Airport, position: &Position, roll: f64) -> Option<String> {
+        let deviation = |end: &RunwayEnd| ((end.heading - roll).rem_euclid(360.0) + 180.0).rem_euclid(360.0) - 180.0;
+        // the distance from the extended centerline of the runway
+        let offset = |end: &RunwayEnd| {
+            end.threshold.map_or(0.0, |threshold| {
+                let angle = (threshold.bearing_deg(position) - end.heading).to_radians();
+                (threshold.distance_nm(position) * angle.sin()).abs()
+            })
+        };
+        airport
+            .runways
+            .iter()
+            .filter(|end| deviation(end).abs() <= self.max_runway_deviation)
+            .min_by(|a, b| offset(a).total_cmp(&offset(b)))
+            .map(|end| end.ident.clone())
+    }
+}
+
+/// FDR comments naming the airport and runway of each takeoff and landing
+pub fn fdr_fields(visits: &[AirportVisit]) -> Vec<Box<dyn FDRField>> {
+    visits
+        .iter()
+        .map(|visit| {
+            Box::new(CommentField {
+                comment: visit.to_string(),
+            }) as Box<dyn FDRField>
+        })
+        .collect()
+}
@@ -0,0 +1,13 @@
+//! Analyses of flight data, from any avionics log source
+//!
+//! Analyses work on the flight data DataFrame, whose columns are named and scaled like those of the Garmin EIS log
+//! (see [`crate::units`]). Columns an analysis needs but that are missing from the data are treated as unknown.
+
+pub mod approach;
+pub mod cooling;
+pub mod exceedance;
+pub mod fuel;
+pub mod landing;
+pub mod leaning;
+pub mod phase;
+pub mod summary;
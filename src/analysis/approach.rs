@@ -0,0 +1,234 @@
+//! Stabilized approach evaluation of each landing
+//!
+//! The final approach is evaluated between two gates, in ft above the landing field. The field elevation is the
+//! baro altitude at touchdown. Between the gates the approach is stabilized if the airspeed stays near the approach
+//! speed, the sink rate stays moderate, the CDIs stay near center and the wings stay near level.
+//!
+//! The configuration (flaps and gear) is part of most stabilized approach criteria, but is not recorded by the
+//! supported avionics, so it is noted as not evaluated. The CDIs follow whatever course is active, so on visual
+//! approaches their limits may be turned off by setting them to None.
+
+use crate::analysis::phase::FlightPhases;
+use crate::data::{column_f64, timestamps};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The gates and limits of a stabilized approach
+#[derive(Debug, Clone)]
+pub struct StabilizedApproachCriteria {
+    /// ft above the field, where the approach must be stabilized
+    pub upper_gate: f64,
+    /// ft above the field, where the evaluation ends
+    pub lower_gate: f64,
+    /// kt, the target approach speed. Defaults to the average airspeed between the gates.
+    pub approach_speed: Option<f64>,
+    /// kt above the approach speed
+    pub max_speed_above: f64,
+    /// kt below the approach speed
+    pub max_speed_below: f64,
+    /// fpm
+    pub max_sink_rate: f64,
+    /// fraction of full scale deflection
+    pub max_hcdi: Option<f64>,
+    /// fraction of full scale deflection
+    pub max_vcdi: Option<f64>,
+    /// deg
+    pub max_bank: f64,
+}
+
+impl Default for StabilizedApproachCriteria {
+    fn default() -> Self {
+        Self {
+            upper_gate: 1000.0,
+            lower_gate: 500.0,
+            approach_speed: None,
+            max_speed_above: 10.0,
+            max_speed_below: 5.0,
+            max_sink_rate: 1000.0,
+            max_hcdi: Some(0.5),
+            max_vcdi: Some(0.5),
+            max_bank: 15.0,
+        }
+    }
+}
+
+/// The evaluation of the final approach of a landing
+#[derive(Debug, Clone, Serialize)]
+pub struct ApproachEvaluation {
+    /// The row of the touchdown
+    pub touchdown: usize,
+    pub touchdown_time: Option<DateTime<Utc>>,
+    /// ft, the baro altitude at touchdown
+    pub field_elevation: f64,
+    /// The time the upper gate was crossed
+    pub upper_gate_time: Option<DateTime<Utc>>,
+    /// The time the lower gate was crossed
+    pub lower_gate_time: Option<DateTime<Utc>>,
+    /// Whether the approach was stabilized. None if the approach did not pass through both gates.
+    pub stabilized: Option<bool>,
+    /// Why the approach was not stabilized
+    pub reasons: Vec<String>,
+    /// Criteria that could not be evaluated
+    pub notes: Vec<String>,
+    /// kt
+    pub approach_speed: Option<f64>,
+    /// kt, the highest airspeed above the approach speed
+    pub max_speed_above: Option<f64>,
+    /// kt, the lowest airspeed below the approach speed
+    pub max_speed_below: Option<f64>,
+    /// fpm
+    pub max_sink_rate: Option<f64>,
+    /// fraction of full scale deflection, in either direction
+    pub max_hcdi: Option<f64>,
+    /// fraction of full scale deflection, in either direction
+    pub max_vcdi: Option<f64>,
+    /// deg, in either direction
+    pub max_bank: Option<f64>,
+}
+
+fn max(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::max)
+}
+
+impl StabilizedApproachCriteria {
+    /// Evaluate the final approach of each landing
+    pub fn evaluate(&self, df: &DataFrame, phases: &FlightPhases) -> Vec<ApproachEvaluation> {
+        let altitude = column_f64(df, "AltB");
+        let ias = column_f64(df, "IAS");
+        let vertical_speed = column_f64(df, "VSpd");
+        let hcdi = column_f64(df, "HCDI");
+        let vcdi = column_f64(df, "VCDI");
+        let roll = column_f64(df, "Roll");
+        let timestamps = timestamps(df);
+
+        phases
+            .landings()
+            .into_iter()
+            .map(|touchdown| {
+                let field_elevation = altitude[..=touchdown]
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .next()
+                    .copied()
+                    .unwrap_or(0.0);
+                let height = |row: usize| altitude[row].map(|a| a - field_elevation);
+
+                // the last descent through the upper gate before touchdown, and then through the lower gate
+                let upper = (0..touchdown)
+                    .rev()
+                    .find(|&r| height(r).is_some_and(|h| h > self.upper_gate));
+                let window = upper.map(|upper| upper + 1).and_then(|start| {
+                    let end = (start..touchdown).find(|&r| height(r).is_some_and(|h| h <= self.lower_gate))?;
+                    Some(start..end)
+                });
+
+                let mut evaluation = ApproachEvaluation {
+                    touchdown,
+                    touchdown_time: timestamps[touchdown],
+                    field_elevation,
+                    upper_gate_time: None,
+                    lower_gate_time: None,
+                    stabilized: None,
+                    reasons: Vec::new(),
+                    notes: vec!["Configuration (flaps and gear) is not recorded".to_string()],
+                    approach_speed: None,
+                    max_speed_above: None,
+                    max_speed_below: None,
+                    max_sink_rate: None,
+                    max_hcdi: None,
+                    max_vcdi: None,
+                    max_bank: None,
+                };
+                let Some(window) = window.filter(|w| !w.is_empty()) else {
+                    evaluation
+                        .notes
+                        .push("The approach did not pass through both gates".to_string());
+                    return evaluation;
+                };
+                evaluation.upper_gate_time = timestamps[window.start];
+                evaluation.lower_gate_time = timestamps[window.end];
+
+                let known =
+                    |values: &[Option<f64>]| values[window.clone()].iter().flatten().copied().collect::<Vec<_>>();
+                let speeds = known(&ias);
+                let approach_speed = self
+                    .approach_speed
+                    .or_else(|| (!speeds.is_empty()).then(|| speeds.iter().sum::<f64>() / speeds.len() as f64));
+                evaluation.approach_speed = approach_speed;
+                if let Some(target) = approach_speed {
+                    evaluation.max_speed_above = max(speeds.iter().map(|s| s - target));
+                    evaluation.max_speed_below = max(speeds.iter().map(|s| target - s));
+                }
+                evaluation.max_sink_rate = max(known(&vertical_speed).into_iter().map(|v| -v));
+                evaluation.max_hcdi = max(known(&hcdi).into_iter().map(f64::abs));
+                evaluation.max_vcdi = max(known(&vcdi).into_iter().map(f64::abs));
+                evaluation.max_bank = max(known(&roll).into_iter().map(f64::abs));
+
+                let mut reasons = Vec::new();
+                let mut check = |value: Option<f64>, limit: Option<f64>, what: &str, unit: &str| match (value, limit) {
+                    (Some(value), Some(limit)) if value > limit => {
+                        reasons.push(format!("{} {:.1}{} exceeds {:.1}{}", what, value, unit, limit, unit))
+                    }
+                    (None, Some(_)) => evaluation.notes.push(format!("{} is not recorded", what)),
+                    _ => (),
+                };
+                check(
+                    evaluation.max_speed_above,
+                    Some(self.max_speed_above),
+                    "Airspeed above approach speed",
+                    " kt",
+                );
+                check(
+                    evaluation.max_speed_below,
+                    Some(self.max_speed_below),
+                    "Airspeed below approach speed",
+                    " kt",
+                );
+                check(evaluation.max_sink_rate, Some(self.max_sink_rate), "Sink rate", " fpm");
+                check(evaluation.max_hcdi, self.max_hcdi, "Lateral deviation (HCDI)", "");
+                check(evaluation.max_vcdi, self.max_vcdi, "Vertical deviation (VCDI)", "");
+                check(evaluation.max_bank, Some(self.max_bank), "Bank angle", " deg");
+
+                evaluation.stabilized = Some(reasons.is_empty());
+                evaluation.reasons = reasons;
+                evaluation
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ApproachEvaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        let result = match self.stabilized {
+            Some(true) => "stabilized",
+            Some(false) => "not stabilized",
+            None => "not evaluated",
+        };
+        writeln!(
+            f,
+            "Landing at {}, field elevation {:.0} ft: {}",
+            time(self.touchdown_time),
+            self.field_elevation,
+            result
+        )?;
+        if self.stabilized.is_some() {
+            writeln!(
+                f,
+                "  Gates crossed at {} and {}",
+                time(self.upper_gate_time),
+                time(self.lower_gate_time)
+            )?;
+        }
+        for reason in &self.reasons {
+            writeln!(f, "  - {}", reason)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "  Note: {}", note)?;
+        }
+        Ok(())
+    }
+}
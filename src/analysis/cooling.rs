@@ -0,0 +1,267 @@
+//! Shock cooling of the cylinders, and the rate of change of their head temperatures
+//!
+//! The Garmin EIS records the fastest cooling of any cylinder as E1 CHT CLD. Here the rate of each cylinder is
+//! computed from its CHT, and shock cooling events are the runs of rows in the descent or approach where a cylinder
+//! cooled faster than a threshold. Each event records what the pilot did around it: the vertical speed during the
+//! event, and the manifold pressure and power before and during it.
+
+use crate::analysis::phase::{FlightPhase, FlightPhases};
+use crate::data::{column_f64, elapsed_seconds, timestamps};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The thresholds used to find shock cooling events
+#[derive(Debug, Clone)]
+pub struct ShockCoolingAnalyzer {
+    /// deg F/min, the CHT rate below which a cylinder is shock cooled
+    pub threshold: f64,
+    /// rows, the window over which the CHT rates are computed
+    pub window_rows: usize,
+    /// rows before an event in which the manifold pressure and power were set before the reduction
+    pub lookback_rows: usize,
+}
+
+impl Default for ShockCoolingAnalyzer {
+    fn default() -> Self {
+        Self {
+            threshold: -50.0,
+            window_rows: 30,
+            lookback_rows: 120,
+        }
+    }
+}
+
+/// A run of rows in which a cylinder cooled faster than the threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct ShockCoolingEvent {
+    /// The first row of the event
+    pub start: usize,
+    /// The row after the last row of the event
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// The cylinder that cooled fastest
+    pub cylinder: u8,
+    /// deg F/min, the fastest cooling rate
+    pub min_rate: f64,
+    /// fpm, the average vertical speed during the event
+    pub avg_vertical_speed: Option<f64>,
+    /// inHg, the highest manifold pressure before the event
+    pub map_before: Option<f64>,
+    /// inHg, the lowest manifold pressure during the event
+    pub map_during: Option<f64>,
+    /// %, the highest power before the event
+    pub power_before: Option<f64>,
+    /// %, the lowest power during the event
+    pub power_during: Option<f64>,
+}
+
+impl ShockCoolingEvent {
+    /// inHg, how far the manifold pressure was reduced leading into the event
+    pub fn map_reduction(&self) -> Option<f64> {
+        Some(self.map_before? - self.map_during?)
+    }
+}
+
+/// The shock cooling of a flight
+#[derive(Debug, Clone, Serialize)]
+pub struct CoolingReport {
+    /// The number of the flight in the log, from 1
+    pub flight: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// deg F/min, the fastest cooling of any cylinder during the descent and approach
+    pub min_rate: Option<f64>,
+    /// The correlation of the fastest cooling rate with the vertical speed, during the descent and approach
+    pub vertical_speed_correlation: Option<f64>,
+    /// The correlation of the fastest cooling rate with the manifold pressure, during the descent and approach
+    pub map_correlation: Option<f64>,
+    pub events: Vec<ShockCoolingEvent>,
+}
+
+/// The cylinder numbers of the CHT columns, in order
+fn cht_cylinders(df: &DataFrame) -> Vec<u8> {
+    let mut cylinders = df
+        .get_column_names()
+        .into_iter()
+        .filter_map(|name| name.strip_prefix("E1 CHT")?.parse::<u8>().ok())
+        .collect::<Vec<_>>();
+    cylinders.sort();
+    cylinders
+}
+
+/// The Pearson correlation of two series, over the rows where both are known
+fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len() as f64;
+    if a.len() < 3 {
+        return None;
+    }
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let covariance = a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum::<f64>();
+    let variance_a = a.iter().map(|x| (x - mean_a).powi(2)).sum::<f64>();
+    let variance_b = b.iter().map(|y| (y - mean_b).powi(2)).sum::<f64>();
+    (variance_a > 0.0 && variance_b > 0.0).then(|| covariance / (variance_a * variance_b).sqrt())
+}
+
+/// The rate of change of each CHT in deg F/min, as the difference across a centered window of rows
+pub fn cht_rates(df: &DataFrame, window_rows: usize) -> Vec<(u8, Vec<Option<f64>>)> {
+    let n = df.height();
+    let half = (window_rows / 2).max(1);
+    let elapsed = elapsed_seconds(df);
+    cht_cylinders(df)
+        .into_iter()
+        .map(|cylinder| {
+            let cht = column_f64(df, &format!("E1 CHT{}", cylinder));
+            let rates = (0..n)
+                .map(|i| {
+                    let (from, to) = (i.saturating_sub(half), (i + half).min(n - 1));
+                    let seconds = elapsed[to]? - elapsed[from]?;
+                    if seconds <= 0.0 {
+                        return None;
+                    }
+                    Some((cht[to]? - cht[from]?) / seconds * 60.0)
+                })
+                .collect();
+            (cylinder, rates)
+        })
+        .collect()
+}
+
+/// Add the rate of change of each CHT, as the "E1 CHT1 Rate" .. columns in deg F/min
+pub fn with_cht_rates(mut df: DataFrame, window_rows: usize) -> PolarsResult<DataFrame> {
+    for (cylinder, rates) in cht_rates(&df, window_rows) {
+        df.with_column(Column::new(format!("E1 CHT{} Rate", cylinder).into(), rates))?;
+    }
+    Ok(df)
+}
+
+impl ShockCoolingAnalyzer {
+    /// Find the shock cooling events of each flight
+    pub fn analyze(&self, df: &DataFrame, phases: &FlightPhases) -> Vec<CoolingReport> {
+        let rates = cht_rates(df, self.window_rows);
+        let vertical_speed = column_f64(df, "VSpd");
+        let map = column_f64(df, "E1 MAP");
+        let power = column_f64(df, "E1 %Pwr");
+        let timestamps = timestamps(df);
+
+        // the fastest cooling cylinder of each row
+        let coldest = (0..df.height())
+            .map(|row| {
+                rates
+                    .iter()
+                    .filter_map(|(cylinder, rates)| Some((*cylinder, rates[row]?)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+            })
+            .collect::<Vec<_>>();
+        let descending = |row: usize| matches!(phases.phases[row], FlightPhase::Descent | FlightPhase::Approach);
+        let cooling = |row: usize| descending(row) && coldest[row].is_some_and(|c| c.1 < self.threshold);
+        let known = |values: &[Option<f64>]| values.iter().flatten().copied().collect::<Vec<_>>();
+        let max = |values: &[Option<f64>]| known(values).into_iter().reduce(f64::max);
+        let min = |values: &[Option<f64>]| known(values).into_iter().reduce(f64::min);
+
+        phases
+            .flights()
+            .into_iter()
+            .enumerate()
+            .map(|(i, flight)| {
+                let mut events = Vec::new();
+                let mut row = flight.start;
+                while row < flight.end {
+                    if !cooling(row) {
+                        row += 1;
+                        continue;
+                    }
+                    let start = row;
+                    while row < flight.end && cooling(row) {
+                        row += 1;
+                    }
+                    let (cylinder, min_rate) = coldest[start..row]
+                        .iter()
+                        .flatten()
+                        .copied()
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .unwrap();
+                    let before = start.saturating_sub(self.lookback_rows).max(flight.start)..start;
+                    let speeds = known(&vertical_speed[start..row]);
+                    events.push(ShockCoolingEvent {
+                        start,
+                        end: row,
+                        start_time: timestamps[start],
+                        end_time: timestamps[row - 1],
+                        cylinder,
+                        min_rate,
+                        avg_vertical_speed: (!speeds.is_empty())
+                            .then(|| speeds.iter().sum::<f64>() / speeds.len() as f64),
+                        map_before: max(&map[before.clone()]),
+                        map_during: min(&map[start..row]),
+                        power_before: max(&power[before]),
+                        power_during: min(&power[start..row]),
+                    });
+                }
+
+                // correlate over the rows of the descent and approach where all values are known
+                let descent = flight.clone().filter(|&row| descending(row)).collect::<Vec<_>>();
+                let paired = |values: &[Option<f64>]| {
+                    descent
+                        .iter()
+                        .filter_map(|&row| Some((coldest[row]?.1, values[row]?)))
+                        .unzip::<f64, f64, Vec<_>, Vec<_>>()
+                };
+                let (rate_vs, vs) = paired(&vertical_speed);
+                let (rate_map, map_values) = paired(&map);
+
+                CoolingReport {
+                    flight: i + 1,
+                    start_time: timestamps[flight.start],
+                    end_time: timestamps[flight.end - 1],
+                    min_rate: descent.iter().filter_map(|&row| Some(coldest[row]?.1)).reduce(f64::min),
+                    vertical_speed_correlation: correlation(&rate_vs, &vs),
+                    map_correlation: correlation(&rate_map, &map_values),
+                    events,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for CoolingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        let value = |v: Option<f64>, precision: usize| v.map_or("-".to_string(), |v| format!("{:.*}", precision, v));
+        writeln!(
+            f,
+            "Flight {} from {} to {}",
+            self.flight,
+            time(self.start_time),
+            time(self.end_time)
+        )?;
+        writeln!(f, "  Fastest cooling in descent  {} deg F/min", value(self.min_rate, 1))?;
+        writeln!(
+            f,
+            "  Correlation of cooling with vertical speed {}, with manifold pressure {}",
+            value(self.vertical_speed_correlation, 2),
+            value(self.map_correlation, 2)
+        )?;
+        if self.events.is_empty() {
+            return writeln!(f, "  No shock cooling");
+        }
+        for event in &self.events {
+            writeln!(
+                f,
+                "  Shock cooling from {} to {}, CHT{} {:.1} deg F/min, VSpd {} fpm, MAP {} to {} inHg, power {} to {} %",
+                time(event.start_time),
+                time(event.end_time),
+                event.cylinder,
+                event.min_rate,
+                value(event.avg_vertical_speed, 0),
+                value(event.map_before, 1),
+                value(event.map_during, 1),
+                value(event.power_before, 0),
+                value(event.power_during, 0)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Exceedances of the engine and electrical limits of an aircraft
+//!
+//! Limits are read from a TOML file listing the limits of each column. For example:
+//!
+//! ```toml
+//! [[limits]]
+//! # a `*` matches the cylinder number, as in E1 CHT1 .. E1 CHT6
+//! column = "E1 CHT*"
+//! max = 500.0
+//!
+//! [[limits]]
+//! column = "E1 OilP"
+//! min = 25.0
+//! max = 100.0
+//! # only checked while the engine is running, as oil pressure is low before start
+//! engine_running = true
+//!
+//! [[limits]]
+//! # shock cooling, in deg F/min
+//! column = "E1 CHT CLD"
+//! min = -50.0
+//! # seconds the limit must be exceeded for before it is reported
+//! min_duration = 2.0
+//! ```
+//!
+//! Limits are in the units of the flight data columns (see [`crate::units`]).
+
+use crate::analysis::phase::PhaseClassifier;
+use crate::data::{column_f64, elapsed_seconds, timestamps};
+use crate::fdr::{EventField, FDRField, MarkerField};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// The limits of an aircraft
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AircraftLimits {
+    #[serde(default)]
+    pub limits: Vec<Limit>,
+}
+
+/// The limits of a column
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    /// The column name. A trailing `*` matches any cylinder number.
+    pub column: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Seconds the limit must be exceeded for before it is reported
+    #[serde(default)]
+    pub min_duration: f64,
+    /// Only check the limit while the engine is running
+    #[serde(default)]
+    pub engine_running: bool,
+}
+
+impl Limit {
+    /// The column matches the column name, or its pattern
+    fn matches(&self, column: &str) -> bool {
+        match self.column.strip_suffix('*') {
+            Some(prefix) => column
+                .strip_prefix(prefix)
+                .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())),
+            None => self.column == column,
+        }
+    }
+}
+
+/// Whether a value was above the maximum or below the minimum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bound {
+    Above,
+    Below,
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Above => f.write_str("above"),
+            Bound::Below => f.write_str("below"),
+        }
+    }
+}
+
+/// A run of consecutive rows in which a column exceeded a limit
+#[derive(Debug, Clone, Serialize)]
+pub struct Exceedance {
+    pub column: String,
+    pub bound: Bound,
+    pub limit: f64,
+    /// The value furthest beyond the limit
+    pub peak: f64,
+    /// The first row of the exceedance
+    pub start: usize,
+    /// The row after the last row of the exceedance
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Seconds from the first row of the exceedance until the value was back within the limit
+    pub duration: f64,
+}
+
+impl fmt::Display for Exceedance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        write!(
+            f,
+            "{} {} {} from {} to {} ({:.0} s), peak {:.1}",
+            self.column,
+            self.bound,
+            self.limit,
+            time(self.start_time),
+            time(self.end_time),
+            self.duration,
+            self.peak
+        )
+    }
+}
+
+impl AircraftLimits {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let limits: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        limits.validate()?;
+        Ok(limits)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for limit in &self.limits {
+            match (limit.min, limit.max) {
+                (None, None) => return Err(format!("limit of '{}' needs a min or a max", limit.column)),
+                (Some(min), Some(max)) if min > max => {
+                    return Err(format!("limit of '{}' has a min above its max", limit.column))
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the flight data against the limits, returning the exceedances in the order they started
+    pub fn check(&self, df: &DataFrame) -> Vec<Exceedance> {
+        let n = df.height();
+        let elapsed = elapsed_seconds(df);
+        let timestamps = timestamps(df);
+
+        // without an RPM column, the engine is assumed to be running
+        let running_rpm = PhaseClassifier::default().running_rpm;
+        let rpm = column_f64(df, "E1 RPM");
+        let running = rpm
+            .iter()
+            .map(|rpm| df.column("E1 RPM").is_err() || rpm.is_some_and(|rpm| rpm > running_rpm))
+            .collect::<Vec<_>>();
+
+        let mut exceedances = Vec::new();
+        for limit in &self.limits {
+            for column in df.get_column_names().into_iter().filter(|c| limit.matches(c)) {
+                let values = column_f64(df, column);
+                let bounds = [(Bound::Above, limit.max), (Bound::Below, limit.min)];
+                for (bound, value) in bounds.into_iter().filter_map(|(b, v)| Some((b, v?))) {
+                    let exceeds = |row: usize| {
+                        (running[row] || !limit.engine_running)
+                            && values[row].is_some_and(|v| match bound {
+                                Bound::Above => v > value,
+                                Bound::Below => v < value,
+                            })
+                    };
+
+                    let mut row = 0;
+                    while row < n {
+                        if !exceeds(row) {
+                            row += 1;
+                            continue;
+                        }
+                        let start = row;
+                        while row < n && exceeds(row) {
+                            row += 1;
+                        }
+                        let run = values[start..row].iter().flatten().copied();
+                        let peak = match bound {
+                            Bound::Above => run.fold(f64::MIN, f64::max),
+                            Bound::Below => run.fold(f64::MAX, f64::min),
+                        };
+                        let duration = match (elapsed[start], elapsed[row.min(n - 1)]) {
+                            (Some(from), Some(to)) => to - from,
+                            _ => 0.0,
+                        };
+                        if duration >= limit.min_duration {
+                            exceedances.push(Exceedance {
+                                column: column.to_string(),
+                                bound,
+                                limit: value,
+                                peak,
+                                start,
+                                end: row,
+                                start_time: timestamps[start],
+                                end_time: timestamps[row - 1],
+                                duration,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        exceedances.sort_by_key(|e| e.start);
+        exceedances
+    }
+}
+
+/// FDR events and markers at the start of each exceedance, for the data the exceedances were found in
+pub fn fdr_fields(exceedances: &[Exceedance], df: &DataFrame) -> Vec<Box<dyn FDRField>> {
+    let elapsed = elapsed_seconds(df);
+    let mut fields: Vec<Box<dyn FDRField>> = Vec::new();
+    for exceedance in exceedances {
+        if let Some(time) = elapsed[exceedance.start] {
+            fields.push(Box::new(EventField { time }));
+            fields.push(Box::new(MarkerField {
+                time: time.round() as i32,
+                text: format!(
+                    "{} {} {} (peak {:.1})",
+                    exceedance.column, exceedance.bound, exceedance.limit, exceedance.peak
+                ),
+            }));
+        }
+    }
+    fields
+}
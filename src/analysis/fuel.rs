@@ -0,0 +1,271 @@
+//! Fuel burned, and the reconciliation of the fuel totalizer with the fuel quantity senders
+//!
+//! The fuel totalizer integrates the fuel flow over time, while the senders measure the fuel left in each tank. The
+//! fuel used by each should agree, and when they do not, either the fuel flow transducer (the K-factor) or the senders
+//! need calibrating. The quantity is averaged over the first and last rows of the log, as the fuel sloshes in the
+//! tanks.
+//!
+//! The endurance at each row is the fuel remaining by the totalizer, from the quantity at the start of the log, at
+//! the current fuel flow.
+
+use crate::analysis::phase::{FlightPhase, FlightPhases};
+use crate::data::{column_f64, elapsed_seconds, integrate, moving_average};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// lbs/gal of avgas, as used by the Garmin EIS to convert the fuel quantity to a weight
+pub const AVGAS_DENSITY: f64 = 6.0;
+
+/// The thresholds used to reconcile the fuel used
+#[derive(Debug, Clone)]
+pub struct FuelAnalyzer {
+    /// rows averaged to read the fuel quantity at the start and end of the log
+    pub quantity_rows: usize,
+    /// fraction of the fuel used by flow, beyond which the totalizer and senders disagree
+    pub tolerance: f64,
+    /// gals kept in reserve, which is not counted in the endurance
+    pub reserve: f64,
+    /// rows, the window of the moving average of the fuel flow used for the endurance
+    pub smoothing_rows: usize,
+}
+
+impl Default for FuelAnalyzer {
+    fn default() -> Self {
+        Self {
+            quantity_rows: 30,
+            tolerance: 0.1,
+            reserve: 0.0,
+            smoothing_rows: 30,
+        }
+    }
+}
+
+/// The fuel burned in a phase of flight, over all of its segments
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseBurn {
+    pub phase: FlightPhase,
+    /// h
+    pub hours: f64,
+    /// gals, by flow
+    pub fuel: f64,
+    /// gph, the average fuel flow
+    pub rate: Option<f64>,
+}
+
+/// The fuel used by a log, by flow and by quantity
+#[derive(Debug, Clone, Serialize)]
+pub struct FuelReport {
+    /// gals, the quantity of both tanks at the start of the log
+    pub start_quantity: Option<f64>,
+    /// gals, the quantity of both tanks at the end of the log
+    pub end_quantity: Option<f64>,
+    /// gals, the drop in the quantity of the left tank
+    pub used_left: Option<f64>,
+    /// gals, the drop in the quantity of the right tank
+    pub used_right: Option<f64>,
+    /// gals, the drop in the quantity of both tanks
+    pub used_quantity: Option<f64>,
+    /// gals, the integrated fuel flow
+    pub used_flow: Option<f64>,
+    /// gals, the fuel used by flow minus by quantity
+    pub difference: Option<f64>,
+    /// Whether the difference is beyond the tolerance. None unless both were recorded.
+    pub disagreement: Option<bool>,
+    /// gph, the fuel used by flow over the time the engine was running
+    pub average_rate: Option<f64>,
+    /// gph, the fuel used by flow over the time in the air
+    pub airborne_rate: Option<f64>,
+    /// h, the endurance at the end of the log
+    pub endurance: Option<f64>,
+    /// The fuel burned in each phase, in the order the phases first occurred
+    pub phases: Vec<PhaseBurn>,
+}
+
+/// The fuel quantity of a tank in gals, from the gals column or else the lbs column
+fn tank_quantity(df: &DataFrame, tank: &str) -> Vec<Option<f64>> {
+    if df.column(&format!("FQty{}", tank)).is_ok() {
+        return column_f64(df, &format!("FQty{}", tank));
+    }
+    column_f64(df, &format!("FQty{}lbs", tank))
+        .into_iter()
+        .map(|lbs| lbs.map(|lbs| lbs / AVGAS_DENSITY))
+        .collect()
+}
+
+/// The fuel quantity of both tanks in gals, in each row that records both
+fn total_quantity(df: &DataFrame) -> Vec<Option<f64>> {
+    tank_quantity(df, "L")
+        .into_iter()
+        .zip(tank_quantity(df, "R"))
+        .map(|(left, right)| Some(left? + right?))
+        .collect()
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// The gals burned in the interval before each row, from the fuel flow
+fn burned(df: &DataFrame) -> Vec<Option<f64>> {
+    let flow = column_f64(df, "E1 FFlow");
+    let elapsed = elapsed_seconds(df);
+    (0..df.height())
+        .map(|row| {
+            let from = row.checked_sub(1)?;
+            integrate(&flow[from..=row], &elapsed[from..=row]).map(|v| v / 3600.0)
+        })
+        .collect()
+}
+
+impl FuelAnalyzer {
+    /// A quantity at the start and end of the log, averaged over its first and last known rows
+    fn quantity_drop(&self, quantity: &[Option<f64>]) -> (Option<f64>, Option<f64>) {
+        let known = quantity.iter().flatten().copied().collect::<Vec<_>>();
+        let rows = self.quantity_rows.min(known.len() / 2).max(1);
+        (
+            mean(known.iter().take(rows).copied()),
+            mean(known.iter().rev().take(rows).copied()),
+        )
+    }
+
+    /// The endurance in hours at each row, from the fuel remaining by the totalizer at the smoothed fuel flow
+    pub fn endurance(&self, df: &DataFrame) -> Vec<Option<f64>> {
+        let (start, _) = self.quantity_drop(&total_quantity(df));
+        let flow = moving_average(&column_f64(df, "E1 FFlow"), self.smoothing_rows);
+        let mut used = 0.0;
+        burned(df)
+            .into_iter()
+            .zip(flow)
+            .map(|(burned, flow)| {
+                used += burned.unwrap_or(0.0);
+                let remaining = (start? - used - self.reserve).max(0.0);
+                flow.filter(|&f| f > 0.0).map(|f| remaining / f)
+            })
+            .collect()
+    }
+
+    /// Add the endurance in hours, as the "Endurance" column
+    pub fn with_endurance(&self, mut df: DataFrame) -> PolarsResult<DataFrame> {
+        let endurance = self.endurance(&df);
+        df.with_column(Column::new("Endurance".into(), endurance))?;
+        Ok(df)
+    }
+
+    /// Reconcile the fuel used by flow and by quantity, and find the fuel burned in each phase
+    pub fn analyze(&self, df: &DataFrame, phases: &FlightPhases) -> FuelReport {
+        let burned = burned(df);
+        let elapsed = elapsed_seconds(df);
+        let hours = |row: usize| Some((elapsed[row]? - elapsed[row.checked_sub(1)?]?) / 3600.0);
+
+        let (start_quantity, end_quantity) = self.quantity_drop(&total_quantity(df));
+        let used = |(start, end): (Option<f64>, Option<f64>)| Some(start? - end?);
+        let used_quantity = used((start_quantity, end_quantity));
+        let used_flow = integrate(&column_f64(df, "E1 FFlow"), &elapsed).map(|v| v / 3600.0);
+        let difference = used_flow.zip(used_quantity).map(|(flow, quantity)| flow - quantity);
+        let disagreement = difference
+            .zip(used_flow)
+            .map(|(difference, flow)| difference.abs() > self.tolerance * flow);
+
+        // the fuel and time of each phase, over the rows where fuel was burned
+        let mut burns: Vec<PhaseBurn> = Vec::new();
+        for (row, (burned, &phase)) in burned.iter().zip(&phases.phases).enumerate() {
+            let (Some(fuel), Some(hours)) = (*burned, hours(row)) else {
+                continue;
+            };
+            match burns.iter_mut().find(|b| b.phase == phase) {
+                Some(burn) => (burn.fuel, burn.hours) = (burn.fuel + fuel, burn.hours + hours),
+                None => burns.push(PhaseBurn {
+                    phase,
+                    hours,
+                    fuel,
+                    rate: None,
+                }),
+            }
+        }
+        for burn in &mut burns {
+            burn.rate = (burn.hours > 0.0).then(|| burn.fuel / burn.hours);
+        }
+        let rate = |burns: &[&PhaseBurn]| {
+            let hours = burns.iter().fold(0.0, |total, b| total + b.hours);
+            (hours > 0.0).then(|| burns.iter().fold(0.0, |total, b| total + b.fuel) / hours)
+        };
+        let running = burns.iter().filter(|b| b.fuel > 0.0).collect::<Vec<_>>();
+        let airborne = burns.iter().filter(|b| b.phase.is_airborne()).collect::<Vec<_>>();
+
+        FuelReport {
+            start_quantity,
+            end_quantity,
+            used_left: used(self.quantity_drop(&tank_quantity(df, "L"))),
+            used_right: used(self.quantity_drop(&tank_quantity(df, "R"))),
+            used_quantity,
+            used_flow,
+            difference,
+            disagreement,
+            average_rate: rate(&running),
+            airborne_rate: rate(&airborne),
+            endurance: self.endurance(df).into_iter().rev().flatten().next(),
+            phases: burns,
+        }
+    }
+}
+
+impl fmt::Display for FuelReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: Option<f64>, precision: usize, unit: &str| {
+            v.map_or("-".to_string(), |v| format!("{:.*}{}", precision, v, unit))
+        };
+        let lbs = |v: Option<f64>| value(v.map(|v| v * AVGAS_DENSITY), 1, " lbs");
+        writeln!(
+            f,
+            "Fuel quantity      {} to {}",
+            value(self.start_quantity, 1, " gals"),
+            value(self.end_quantity, 1, " gals")
+        )?;
+        writeln!(
+            f,
+            "Used by quantity   {} ({}), left {}, right {}",
+            value(self.used_quantity, 1, " gals"),
+            lbs(self.used_quantity),
+            value(self.used_left, 1, " gals"),
+            value(self.used_right, 1, " gals")
+        )?;
+        writeln!(
+            f,
+            "Used by flow       {} ({})",
+            value(self.used_flow, 1, " gals"),
+            lbs(self.used_flow)
+        )?;
+        let agreement = match self.disagreement {
+            Some(true) => ", totalizer and senders disagree",
+            Some(false) => ", totalizer and senders agree",
+            None => "",
+        };
+        writeln!(
+            f,
+            "Difference         {}{}",
+            value(self.difference, 1, " gals"),
+            agreement
+        )?;
+        writeln!(
+            f,
+            "Average burn       {} running, {} airborne",
+            value(self.average_rate, 1, " gph"),
+            value(self.airborne_rate, 1, " gph")
+        )?;
+        writeln!(f, "Endurance at end   {}", value(self.endurance, 1, " h"))?;
+        writeln!(f, "  Phase          Hours    Fuel     Rate")?;
+        for burn in &self.phases {
+            writeln!(
+                f,
+                "  {:<13} {:>6.2} {:>7.1} {:>8}",
+                burn.phase.name(),
+                burn.hours,
+                burn.fuel,
+                value(burn.rate, 1, "")
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Landing quality metrics, from the touchdown of each landing
+//!
+//! OnGrnd is set some seconds after the actual touchdown, once the airspeed has decayed, so the touchdown is
+//! estimated as the start of the last run of rows before OnGrnd is set in which the baro altitude settled within a
+//! few feet of the altitude on the ground. The float is the time and distance from descending through a height above
+//! the field (the flare) to the touchdown.
+//!
+//! The crab is the heading minus the track, and the crosswind is the component of the wind across the track at
+//! touchdown, positive from the right. The wind is averaged over the approach before the flare, as the wind estimate
+//! degrades close to the ground and is not recorded on it. The wind direction is assumed to have the same reference
+//! as the track.
+
+use crate::analysis::phase::FlightPhases;
+use crate::data::{column_f64, elapsed_seconds, timestamps};
+use crate::fdr::{EventField, FDRField, MarkerField};
+use crate::geo::positions;
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The thresholds used to find the touchdown and flare
+#[derive(Debug, Clone)]
+pub struct LandingAnalyzer {
+    /// rows before OnGrnd is set in which the touchdown is searched for
+    pub lookback_rows: usize,
+    /// ft, how close to the altitude on the ground the baro altitude settles at touchdown
+    pub settle_height: f64,
+    /// ft above the field, below which the aircraft is floating
+    pub float_height: f64,
+    /// rows before the flare over which the wind is averaged
+    pub wind_rows: usize,
+}
+
+impl Default for LandingAnalyzer {
+    fn default() -> Self {
+        Self {
+            lookback_rows: 20,
+            settle_height: 2.0,
+            float_height: 10.0,
+            wind_rows: 30,
+        }
+    }
+}
+
+/// The metrics of a landing
+#[derive(Debug, Clone, Serialize)]
+pub struct LandingMetrics {
+    /// The row of the estimated touchdown
+    pub touchdown: usize,
+    pub touchdown_time: Option<DateTime<Utc>>,
+    /// The row at which OnGrnd was set
+    pub on_ground: usize,
+    /// fpm
+    pub vertical_speed: Option<f64>,
+    /// G, the highest load factor around the touchdown
+    pub load_factor: Option<f64>,
+    /// kt
+    pub airspeed: Option<f64>,
+    /// kt
+    pub ground_speed: Option<f64>,
+    /// deg
+    pub pitch: Option<f64>,
+    /// s, from descending through the float height to the touchdown
+    pub float_time: Option<f64>,
+    /// ft, from descending through the float height to the touchdown
+    pub float_distance: Option<f64>,
+    /// deg, the heading minus the track, positive when the nose points right of the track
+    pub crab: Option<f64>,
+    /// kt, positive from the right
+    pub crosswind: Option<f64>,
+    /// kt, negative for a tailwind
+    pub headwind: Option<f64>,
+}
+
+/// The difference between two angles, from -180 up to 180 degrees
+fn angle_difference(a: f64, b: f64) -> f64 {
+    (a - b + 180.0).rem_euclid(360.0) - 180.0
+}
+
+impl LandingAnalyzer {
+    /// Measure each landing
+    pub fn analyze(&self, df: &DataFrame, phases: &FlightPhases) -> Vec<LandingMetrics> {
+        let altitude = column_f64(df, "AltB");
+        let vertical_speed = column_f64(df, "VSpd");
+        let normal = column_f64(df, "NormAc");
+        let ias = column_f64(df, "IAS");
+        let ground_speed = column_f64(df, "GndSpd");
+        let pitch = column_f64(df, "Pitch");
+        let heading = column_f64(df, "HDG");
+        let track = column_f64(df, "TRK");
+        let wind_speed = column_f64(df, "WndSpd");
+        let wind_direction = column_f64(df, "WndDr");
+        let elapsed = elapsed_seconds(df);
+        let timestamps = timestamps(df);
+        let positions = positions(df);
+
+        phases
+            .landings()
+            .into_iter()
+            .map(|on_ground| {
+                let field = altitude[on_ground];
+                let height = |row: usize| Some(altitude[row]? - field?);
+                let first = on_ground.saturating_sub(self.lookback_rows);
+
+                // the baro altitude settles once on the ground
+                let touchdown = (first..on_ground)
+                    .rev()
+                    .take_while(|&r| height(r).is_some_and(|h| h.abs() <= self.settle_height))
+                    .last()
+                    .unwrap_or(on_ground);
+
+                // the flare starts at the last descent through the float height
+                let flare = (0..touchdown)
+                    .rev()
+                    .find(|&r| height(r).is_some_and(|h| h > self.float_height))
+                    .map(|r| r + 1);
+                let float_time = flare.and_then(|flare| Some(elapsed[touchdown]? - elapsed[flare]?));
+                let float_distance = flare.map(|flare| {
+                    let distance = positions[flare..=touchdown]
+                        .windows(2)
+                        .filter_map(|p| Some(p[0]?.distance_nm(&p[1]?)))
+                        .sum::<f64>();
+                    distance * 6076.12
+                });
+
+                let around = touchdown.saturating_sub(2)..(touchdown + 3).min(df.height());
+                let load_factor = normal[around].iter().flatten().map(|g| g + 1.0).reduce(f64::max);
+                let crab = heading[touchdown]
+                    .zip(track[touchdown])
+                    .map(|(h, t)| angle_difference(h, t));
+
+                // the wind before the flare, averaged as vectors
+                let wind_end = flare.unwrap_or(touchdown);
+                let (north, east, count) = (wind_end.saturating_sub(self.wind_rows)..wind_end)
+                    .filter_map(|r| Some((wind_speed[r]?, wind_direction[r]?.to_radians())))
+                    .fold((0.0, 0.0, 0), |(n, e, c), (speed, direction)| {
+                        (n + speed * direction.cos(), e + speed * direction.sin(), c + 1)
+                    });
+                let wind = (count > 0).then(|| {
+                    let (north, east) = (north / count as f64, east / count as f64);
+                    (north.hypot(east), east.atan2(north).to_degrees())
+                });
+                let components = wind.zip(track[touchdown]).map(|((speed, direction), track)| {
+                    let relative = angle_difference(direction, track).to_radians();
+                    (speed * relative.sin(), speed * relative.cos())
+                });
+
+                LandingMetrics {
+                    touchdown,
+                    touchdown_time: timestamps[touchdown],
+                    on_ground,
+                    vertical_speed: vertical_speed[touchdown],
+                    load_factor,
+                    airspeed: ias[touchdown],
+                    ground_speed: ground_speed[touchdown],
+                    pitch: pitch[touchdown],
+                    float_time,
+                    float_distance,
+                    crab,
+                    crosswind: components.map(|c| c.0),
+                    headwind: components.map(|c| c.1),
+                }
+            })
+            .collect()
+    }
+}
+
+/// FDR events and markers at each touchdown, for the data the landings were measured in
+pub fn fdr_fields(landings: &[LandingMetrics], df: &DataFrame) -> Vec<Box<dyn FDRField>> {
+    let elapsed = elapsed_seconds(df);
+    let mut fields: Vec<Box<dyn FDRField>> = Vec::new();
+    for landing in landings {
+        if let Some(time) = elapsed[landing.touchdown] {
+            let mut text = "Touchdown".to_string();
+            if let Some(vertical_speed) = landing.vertical_speed {
+                text.push_str(&format!(" {:.0} fpm", vertical_speed));
+            }
+            if let Some(load_factor) = landing.load_factor {
+                text.push_str(&format!(" {:.2} G", load_factor));
+            }
+            fields.push(Box::new(EventField { time }));
+            fields.push(Box::new(MarkerField {
+                time: time.round() as i32,
+                text,
+            }));
+        }
+    }
+    fields
+}
+
+impl fmt::Display for LandingMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: Option<f64>, precision: usize, unit: &str| {
+            v.map_or("-".to_string(), |v| format!("{:.*}{}", precision, v, unit))
+        };
+        let time = self
+            .touchdown_time
+            .map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        writeln!(f, "Touchdown at {}", time)?;
+        writeln!(f, "  Vertical speed  {}", value(self.vertical_speed, 0, " fpm"))?;
+        writeln!(f, "  Load factor     {}", value(self.load_factor, 2, " G"))?;
+        writeln!(
+            f,
+            "  Speed           {} IAS, {} ground",
+            value(self.airspeed, 0, " kt"),
+            value(self.ground_speed, 0, " kt")
+        )?;
+        writeln!(f, "  Pitch           {}", value(self.pitch, 1, " deg"))?;
+        writeln!(
+            f,
+            "  Float           {}, {}",
+            value(self.float_time, 0, " s"),
+            value(self.float_distance, 0, " ft")
+        )?;
+        writeln!(f, "  Crab            {}", value(self.crab, 1, " deg"))?;
+        writeln!(
+            f,
+            "  Wind            {} crosswind{}, {} headwind",
+            value(self.crosswind.map(f64::abs), 1, " kt"),
+            match self.crosswind {
+                Some(c) if c < 0.0 => " from the left",
+                Some(c) if c > 0.0 => " from the right",
+                _ => "",
+            },
+            value(self.headwind, 1, " kt")
+        )
+    }
+}
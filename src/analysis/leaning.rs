@@ -0,0 +1,269 @@
+//! Leaning sweeps and the GAMI spread of the engine
+//!
+//! A leaning sweep is a steady reduction of the fuel flow in cruise, during which the EGT of each cylinder rises to
+//! its peak and then falls as the mixture becomes lean of peak. The fuel flow at which each cylinder peaks differs
+//! with how evenly the injectors share the fuel between the cylinders. The difference between the highest and the
+//! lowest of these fuel flows is the GAMI spread. The cylinder peaking at the highest fuel flow peaks first while
+//! leaning, and is the leanest cylinder.
+
+use crate::analysis::phase::{FlightPhase, FlightPhases};
+use crate::data::{column_f64, moving_average, timestamps};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The thresholds used to find leaning sweeps
+#[derive(Debug, Clone)]
+pub struct LeaningAnalyzer {
+    /// gph, the smallest drop in fuel flow that counts as a sweep
+    pub min_flow_drop: f64,
+    /// gph, how far the fuel flow may rise above its lowest value during a sweep, allowing for noise
+    pub flow_tolerance: f64,
+    /// deg F, how far the EGT of a cylinder must rise to and fall from its peak for the peak to be found
+    pub min_egt_change: f64,
+    /// inHg, the largest change of manifold pressure during a sweep, which is made at a constant power setting
+    pub max_map_change: f64,
+    /// rpm, the largest change of engine speed during a sweep
+    pub max_rpm_change: f64,
+    /// rows, the window of the moving average of the fuel flow and EGTs
+    pub smoothing_rows: usize,
+}
+
+impl Default for LeaningAnalyzer {
+    fn default() -> Self {
+        Self {
+            min_flow_drop: 1.0,
+            flow_tolerance: 0.15,
+            min_egt_change: 10.0,
+            max_map_change: 1.0,
+            max_rpm_change: 100.0,
+            smoothing_rows: 5,
+        }
+    }
+}
+
+/// The peak EGT of a cylinder during a sweep
+#[derive(Debug, Clone, Serialize)]
+pub struct CylinderPeak {
+    pub cylinder: u8,
+    /// The row of the peak
+    pub row: usize,
+    /// deg F
+    pub egt: f64,
+    /// gph, the fuel flow at the peak
+    pub fuel_flow: f64,
+}
+
+/// A leaning sweep, and the peak of each cylinder that peaked during it
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaningSweep {
+    /// The first row of the sweep
+    pub start: usize,
+    /// The row after the last row of the sweep
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// gph
+    pub start_flow: f64,
+    /// gph
+    pub end_flow: f64,
+    /// The cylinders that peaked, in cylinder order
+    pub peaks: Vec<CylinderPeak>,
+    /// The numbers of the cylinders that did not peak during the sweep
+    pub missing: Vec<u8>,
+}
+
+/// The cylinder numbers of the EGT columns, in order
+fn egt_cylinders(df: &DataFrame) -> Vec<u8> {
+    let mut cylinders = df
+        .get_column_names()
+        .into_iter()
+        .filter_map(|name| name.strip_prefix("E1 EGT")?.parse::<u8>().ok())
+        .collect::<Vec<_>>();
+    cylinders.sort();
+    cylinders
+}
+
+impl LeaningSweep {
+    /// The GAMI spread, in gph. None unless at least two cylinders peaked.
+    pub fn spread(&self) -> Option<f64> {
+        if self.peaks.len() < 2 {
+            return None;
+        }
+        let flows = self.peaks.iter().map(|p| p.fuel_flow);
+        Some(flows.clone().fold(f64::MIN, f64::max) - flows.fold(f64::MAX, f64::min))
+    }
+
+    /// The cylinder that peaked first while leaning, which is the one peaking at the highest fuel flow
+    pub fn first_to_peak(&self) -> Option<u8> {
+        self.peaks
+            .iter()
+            .max_by(|a, b| a.fuel_flow.total_cmp(&b.fuel_flow))
+            .map(|p| p.cylinder)
+    }
+
+    /// The fuel flow and EGTs of the rows of the sweep, for charting the EGT of each cylinder against fuel flow
+    pub fn chart_data(&self, df: &DataFrame) -> PolarsResult<DataFrame> {
+        let mut columns = vec![col("E1 FFlow")];
+        columns.extend(egt_cylinders(df).iter().map(|c| col(format!("E1 EGT{}", c))));
+        if df.column("Timestamp").is_ok() {
+            columns.insert(0, col("Timestamp"));
+        }
+        df.slice(self.start as i64, self.end - self.start)
+            .lazy()
+            .select(columns)
+            .collect()
+    }
+}
+
+impl fmt::Display for LeaningSweep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        writeln!(
+            f,
+            "Sweep from {} to {}, {:.1} to {:.1} gph",
+            time(self.start_time),
+            time(self.end_time),
+            self.start_flow,
+            self.end_flow
+        )?;
+        writeln!(f, "  Cylinder  Peak EGT  Fuel flow")?;
+        for peak in &self.peaks {
+            writeln!(f, "  {:>8}  {:>8.0}  {:>9.2}", peak.cylinder, peak.egt, peak.fuel_flow)?;
+        }
+        for cylinder in &self.missing {
+            writeln!(f, "  {:>8}  {:>8}  {:>9}", cylinder, "-", "-")?;
+        }
+        match (self.spread(), self.first_to_peak()) {
+            (Some(spread), Some(first)) => {
+                writeln!(f, "  GAMI spread {:.2} gph, cylinder {} peaks first", spread, first)
+            }
+            _ => writeln!(f, "  GAMI spread unknown, too few cylinders peaked"),
+        }
+    }
+}
+
+impl LeaningAnalyzer {
+    /// Find the leaning sweeps in cruise, and the peak of each cylinder during them
+    pub fn find_sweeps(&self, df: &DataFrame, phases: &FlightPhases) -> Vec<LeaningSweep> {
+        let n = df.height();
+        let flow = moving_average(&column_f64(df, "E1 FFlow"), self.smoothing_rows);
+        let cylinders = egt_cylinders(df);
+        let egts = cylinders
+            .iter()
+            .map(|c| moving_average(&column_f64(df, &format!("E1 EGT{}", c)), self.smoothing_rows))
+            .collect::<Vec<_>>();
+        let map = moving_average(&column_f64(df, "E1 MAP"), self.smoothing_rows);
+        let rpm = moving_average(&column_f64(df, "E1 RPM"), self.smoothing_rows);
+        let timestamps = timestamps(df);
+
+        // a sweep continues while the fuel flow stays near or below its lowest value since the start of the sweep
+        let mut candidates = Vec::new();
+        let mut row = 0;
+        while row < n {
+            let (Some(start_flow), FlightPhase::Cruise) = (flow[row], phases.phases[row]) else {
+                row += 1;
+                continue;
+            };
+            let start = row;
+            let (mut lowest, mut lowest_row) = (start_flow, row);
+            row += 1;
+            while row < n && phases.phases[row] == FlightPhase::Cruise {
+                match flow[row] {
+                    Some(f) if f <= lowest + self.flow_tolerance => {
+                        if f < lowest {
+                            (lowest, lowest_row) = (f, row);
+                        }
+                    }
+                    _ => break,
+                }
+                row += 1;
+            }
+            // the sweep runs from its highest to its lowest fuel flow, leaving out steady cruise before and after it
+            let highest = flow[start..lowest_row]
+                .iter()
+                .flatten()
+                .copied()
+                .fold(start_flow, f64::max);
+            if highest - lowest >= self.min_flow_drop {
+                let start = (start..lowest_row)
+                    .rev()
+                    .find(|&r| flow[r].is_some_and(|f| f >= highest - self.flow_tolerance))
+                    .unwrap_or(start);
+                candidates.push((start, lowest_row + 1));
+            }
+        }
+
+        // a change of throttle or propeller is a change of power, not a sweep
+        let change = |values: &[Option<f64>]| {
+            let known = values.iter().flatten().copied();
+            known.clone().fold(f64::MIN, f64::max) - known.fold(f64::MAX, f64::min)
+        };
+        candidates
+            .into_iter()
+            .filter(|&(start, end)| {
+                change(&map[start..end]) <= self.max_map_change && change(&rpm[start..end]) <= self.max_rpm_change
+            })
+            .filter_map(|(start, end)| {
+                let mut peaks = Vec::new();
+                let mut missing = Vec::new();
+                for (&cylinder, egt) in cylinders.iter().zip(&egts) {
+                    match self.find_peak(&egt[start..end]) {
+                        Some(offset) if flow[start + offset].is_some() => peaks.push(CylinderPeak {
+                            cylinder,
+                            row: start + offset,
+                            egt: egt[start + offset].unwrap(),
+                            fuel_flow: flow[start + offset].unwrap(),
+                        }),
+                        _ => missing.push(cylinder),
+                    }
+                }
+                // a drop in fuel flow without any cylinder peaking is a power reduction, not a sweep
+                (!peaks.is_empty()).then(|| LeaningSweep {
+                    start,
+                    end,
+                    start_time: timestamps[start],
+                    end_time: timestamps[end - 1],
+                    start_flow: flow[start].unwrap(),
+                    end_flow: flow[end - 1].unwrap_or_default(),
+                    peaks,
+                    missing,
+                })
+            })
+            .collect()
+    }
+
+    /// The offset of the peak EGT, if it rose to and fell from the peak
+    fn find_peak(&self, egt: &[Option<f64>]) -> Option<usize> {
+        let (offset, peak) = egt
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| Some((i, (*v)?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let before = egt[..offset].iter().flatten().copied().fold(f64::MAX, f64::min);
+        let after = egt[offset..].iter().flatten().copied().fold(f64::MAX, f64::min);
+        (peak - before >= self.min_egt_change && peak - after >= self.min_egt_change).then_some(offset)
+    }
+}
+
+/// The peaks of each sweep as a table, with a row per cylinder and sweep
+pub fn peaks_dataframe(sweeps: &[LeaningSweep]) -> PolarsResult<DataFrame> {
+    let rows = sweeps
+        .iter()
+        .enumerate()
+        .flat_map(|(i, sweep)| sweep.peaks.iter().map(move |peak| (i as u32 + 1, peak)))
+        .collect::<Vec<_>>();
+    DataFrame::new(vec![
+        Column::new("Sweep".into(), rows.iter().map(|(i, _)| *i).collect::<Vec<_>>()),
+        Column::new(
+            "Cylinder".into(),
+            rows.iter().map(|(_, p)| p.cylinder as u32).collect::<Vec<_>>(),
+        ),
+        Column::new("PeakEGT".into(), rows.iter().map(|(_, p)| p.egt).collect::<Vec<_>>()),
+        Column::new(
+            "PeakFFlow".into(),
+            rows.iter().map(|(_, p)| p.fuel_flow).collect::<Vec<_>>(),
+        ),
+    ])
+}
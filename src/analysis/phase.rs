@@ -0,0 +1,352 @@
+//! Classify each row of flight data into a phase of flight
+//!
+//! Rows are first split into ground and airborne using OnGrnd (or the speed, when OnGrnd is not recorded). Each
+//! airborne run is a flight, and the ground rows around it are the taxi, takeoff and landing roll phases. Within a
+//! flight, the rows after the initial climb and before the approach are classified by the smoothed vertical speed.
+//!
+//! The data is assumed to be recorded at about one row per second, as in the Garmin EIS log. Durations are counted
+//! in rows.
+
+use crate::data::{column_f64, elapsed_seconds, moving_average, timestamps};
+use crate::fdr::MarkerField;
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use std::ops::Range;
+
+/// A phase of flight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum FlightPhase {
+    Preflight,
+    TaxiOut,
+    Takeoff,
+    InitialClimb,
+    Climb,
+    Cruise,
+    Descent,
+    Approach,
+    LandingRoll,
+    TaxiIn,
+    Shutdown,
+}
+
+impl FlightPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlightPhase::Preflight => "Preflight",
+            FlightPhase::TaxiOut => "Taxi out",
+            FlightPhase::Takeoff => "Takeoff",
+            FlightPhase::InitialClimb => "Initial climb",
+            FlightPhase::Climb => "Climb",
+            FlightPhase::Cruise => "Cruise",
+            FlightPhase::Descent => "Descent",
+            FlightPhase::Approach => "Approach",
+            FlightPhase::LandingRoll => "Landing roll",
+            FlightPhase::TaxiIn => "Taxi in",
+            FlightPhase::Shutdown => "Shutdown",
+        }
+    }
+
+    /// The aircraft is in the air during this phase
+    pub fn is_airborne(&self) -> bool {
+        matches!(
+            self,
+            FlightPhase::InitialClimb
+                | FlightPhase::Climb
+                | FlightPhase::Cruise
+                | FlightPhase::Descent
+                | FlightPhase::Approach
+        )
+    }
+}
+
+impl fmt::Display for FlightPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A run of consecutive rows in the same phase
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseSegment {
+    pub phase: FlightPhase,
+    /// The first row of the segment
+    pub start: usize,
+    /// The row after the last row of the segment
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// The thresholds used to classify phases
+#[derive(Debug, Clone)]
+pub struct PhaseClassifier {
+    /// kt, ground speed above which the aircraft is moving
+    pub moving_speed: f64,
+    /// kt, ground speed above which the aircraft is no longer taxiing
+    pub taxi_speed: f64,
+    /// kt, indicated airspeed (or ground speed) above which the aircraft is flying, when OnGrnd is not recorded
+    pub flying_speed: f64,
+    /// rpm, engine speed above which the engine is running
+    pub running_rpm: f64,
+    /// fpm, smoothed vertical speed beyond which the aircraft is climbing or descending
+    pub climb_rate: f64,
+    /// ft above the departure, below which the aircraft is in its initial climb
+    pub initial_climb_height: f64,
+    /// ft above the arrival, below which the aircraft is on approach
+    pub approach_height: f64,
+    /// rows, the window of the moving average of the vertical speed
+    pub smoothing_rows: usize,
+    /// rows, the shortest climb, cruise or descent. Shorter ones are merged into the phase before them.
+    pub min_phase_rows: usize,
+    /// rows, the shortest time airborne that counts as a flight
+    pub min_airborne_rows: usize,
+}
+
+impl Default for PhaseClassifier {
+    fn default() -> Self {
+        Self {
+            moving_speed: 3.0,
+            taxi_speed: 15.0,
+            flying_speed: 40.0,
+            running_rpm: 500.0,
+            climb_rate: 300.0,
+            initial_climb_height: 1000.0,
+            approach_height: 1000.0,
+            smoothing_rows: 30,
+            min_phase_rows: 60,
+            min_airborne_rows: 10,
+        }
+    }
+}
+
+/// The phase of each row of flight data
+#[derive(Debug, Clone)]
+pub struct FlightPhases {
+    /// The phase of each row
+    pub phases: Vec<FlightPhase>,
+    /// The phase boundaries, in order
+    pub segments: Vec<PhaseSegment>,
+}
+
+/// Split values into runs of equal values, as (start, end, value)
+fn runs<T: PartialEq + Copy>(values: &[T]) -> Vec<(usize, usize, T)> {
+    let mut runs: Vec<(usize, usize, T)> = Vec::new();
+    for (i, &value) in values.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.2 == value => run.1 = i + 1,
+            _ => runs.push((i, i + 1, value)),
+        }
+    }
+    runs
+}
+
+/// Fill missing values with the previous value, and leading missing values with the first value
+fn fill_missing<T: Copy>(values: &[Option<T>], default: T) -> Vec<T> {
+    let first = values.iter().flatten().next().copied().unwrap_or(default);
+    values
+        .iter()
+        .scan(first, |last, v| {
+            *last = v.unwrap_or(*last);
+            Some(*last)
+        })
+        .collect()
+}
+
+impl PhaseClassifier {
+    /// Classify the phase of each row of flight data
+    pub fn classify(&self, df: &DataFrame) -> FlightPhases {
+        let n = df.height();
+        let ias = column_f64(df, "IAS");
+        let ground_speed = column_f64(df, "GndSpd")
+            .into_iter()
+            .zip(&ias)
+            .map(|(gs, ias)| gs.or(*ias).unwrap_or(0.0))
+            .collect::<Vec<_>>();
+        let altitude = fill_missing(&column_f64(df, "AltB"), 0.0);
+        let rpm = column_f64(df, "E1 RPM");
+        let vertical_speed = moving_average(&column_f64(df, "VSpd"), self.smoothing_rows);
+
+        let on_ground = column_f64(df, "OnGrnd");
+        let airborne = if on_ground.iter().any(|v| v.is_some()) {
+            fill_missing(
+                &on_ground.iter().map(|v| v.map(|v| v == 0.0)).collect::<Vec<_>>(),
+                false,
+            )
+        } else {
+            // without airspeed, as from a GPS, the ground speed will do
+            ias.iter()
+                .zip(&ground_speed)
+                .map(|(ias, &gs)| ias.unwrap_or(gs) > self.flying_speed)
+                .collect()
+        };
+
+        // flights are the airborne runs that are long enough, as (liftoff, touchdown) rows
+        let flights = runs(&airborne)
+            .into_iter()
+            .filter(|&(start, end, air)| air && end - start >= self.min_airborne_rows)
+            .map(|(start, end, _)| (start, end))
+            .collect::<Vec<_>>();
+
+        let mut phases = vec![FlightPhase::Preflight; n];
+        if let Some(first_move) = ground_speed.iter().position(|&gs| gs > self.moving_speed) {
+            phases[first_move..].fill(FlightPhase::TaxiOut);
+        }
+
+        // the end of the landing roll of the flight before, which the takeoff roll of the next cannot go back past
+        let mut ground_start = 0;
+        for (i, &(liftoff, touchdown)) in flights.iter().enumerate() {
+            // the takeoff roll is the acceleration through taxi speed up to liftoff
+            let mut roll_start = liftoff;
+            while roll_start > ground_start && ground_speed[roll_start - 1] > self.taxi_speed {
+                roll_start -= 1;
+            }
+            while roll_start > ground_start && ground_speed[roll_start - 1] < ground_speed[roll_start] {
+                roll_start -= 1;
+            }
+            phases[roll_start..liftoff].fill(FlightPhase::Takeoff);
+
+            // airborne rows, before the approach and after the initial climb, are classified by vertical speed. A log
+            // that starts or ends in the air has no departure or arrival field to measure the height above.
+            let departure = altitude[liftoff];
+            let climb_end = match liftoff {
+                0 => 0,
+                _ => (liftoff..touchdown)
+                    .find(|&r| altitude[r] - departure >= self.initial_climb_height)
+                    .unwrap_or(touchdown),
+            };
+            let mut approach_start = touchdown;
+            if touchdown < n {
+                let arrival = altitude[touchdown];
+                while approach_start > climb_end && altitude[approach_start - 1] - arrival < self.approach_height {
+                    approach_start -= 1;
+                }
+            }
+
+            let enroute = (climb_end..approach_start)
+                .map(|r| match vertical_speed[r] {
+                    Some(vs) if vs > self.climb_rate => FlightPhase::Climb,
+                    Some(vs) if vs < -self.climb_rate => FlightPhase::Descent,
+                    _ => FlightPhase::Cruise,
+                })
+                .collect::<Vec<_>>();
+            phases[liftoff..climb_end].fill(FlightPhase::InitialClimb);
+            phases[climb_end..approach_start].copy_from_slice(&self.merge_short_runs(enroute));
+            phases[approach_start..touchdown].fill(FlightPhase::Approach);
+
+            if touchdown == n {
+                continue;
+            }
+
+            // the landing roll ends when slowed to taxi speed. On a touch-and-go, which lifts off again before that, it
+            // ends at the slowest row before the next liftoff.
+            let next_liftoff = flights.get(i + 1).map_or(n, |&(liftoff, _)| liftoff);
+            let mut roll_end = touchdown + 1;
+            while roll_end < next_liftoff && ground_speed[roll_end] > self.taxi_speed {
+                roll_end += 1;
+            }
+            if roll_end == next_liftoff && next_liftoff < n {
+                let slowest = (touchdown..next_liftoff)
+                    .min_by(|&a, &b| ground_speed[a].total_cmp(&ground_speed[b]))
+                    .unwrap_or(touchdown);
+                roll_end = slowest + 1;
+            }
+            phases[touchdown..roll_end].fill(FlightPhase::LandingRoll);
+            ground_start = roll_end;
+
+            // taxiing back to take off again, or taxiing in after the last flight until the engine is shut down
+            if i + 1 < flights.len() {
+                phases[roll_end..flights[i + 1].0].fill(FlightPhase::TaxiOut);
+            } else {
+                let last_running = if rpm.iter().any(|v| v.is_some()) {
+                    (roll_end..n).rev().find(|&r| rpm[r].unwrap_or(0.0) > self.running_rpm)
+                } else {
+                    (roll_end..n).rev().find(|&r| ground_speed[r] > self.moving_speed)
+                };
+                let shutdown = last_running.map_or(roll_end, |r| r + 1);
+                phases[roll_end..shutdown].fill(FlightPhase::TaxiIn);
+                phases[shutdown..].fill(FlightPhase::Shutdown);
+            }
+        }
+
+        let timestamps = timestamps(df);
+        let segments = runs(&phases)
+            .into_iter()
+            .map(|(start, end, phase)| PhaseSegment {
+                phase,
+                start,
+                end,
+                start_time: timestamps[start],
+                end_time: timestamps[end - 1],
+            })
+            .collect();
+
+        FlightPhases { phases, segments }
+    }
+
+    /// Merge runs shorter than the minimum phase length into the run before them
+    fn merge_short_runs(&self, mut phases: Vec<FlightPhase>) -> Vec<FlightPhase> {
+        let mut previous: Option<FlightPhase> = None;
+        for (start, end, phase) in runs(&phases) {
+            match previous {
+                Some(previous) if end - start < self.min_phase_rows => phases[start..end].fill(previous),
+                _ => previous = Some(phase),
+            }
+        }
+        phases
+    }
+}
+
+impl FlightPhases {
+    /// The rows at which the aircraft lifted off
+    pub fn takeoffs(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .filter(|s| s.phase == FlightPhase::InitialClimb)
+            .map(|s| s.start)
+            .collect()
+    }
+
+    /// The rows at which the aircraft touched down
+    pub fn landings(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .filter(|s| s.phase == FlightPhase::LandingRoll)
+            .map(|s| s.start)
+            .collect()
+    }
+
+    /// The rows in the air of each flight, from liftoff up to touchdown
+    pub fn flights(&self) -> Vec<Range<usize>> {
+        let mut flights: Vec<Range<usize>> = Vec::new();
+        for segment in self.segments.iter().filter(|s| s.phase.is_airborne()) {
+            match flights.last_mut() {
+                Some(flight) if flight.end == segment.start => flight.end = segment.end,
+                _ => flights.push(segment.start..segment.end),
+            }
+        }
+        flights
+    }
+
+    /// Add the phase of each row to the data, as the "Phase" column
+    pub fn with_phase_column(&self, mut df: DataFrame) -> PolarsResult<DataFrame> {
+        let names = self.phases.iter().map(|p| p.name()).collect::<Vec<_>>();
+        df.with_column(Column::new("Phase".into(), names))?;
+        Ok(df)
+    }
+
+    /// FDR markers at the start of each phase, for the data the phases were classified from
+    pub fn markers(&self, df: &DataFrame) -> Vec<MarkerField> {
+        let elapsed = elapsed_seconds(df);
+        self.segments
+            .iter()
+            .filter_map(|s| {
+                Some(MarkerField {
+                    time: elapsed[s.start]?.round() as i32,
+                    text: s.phase.name().to_string(),
+                })
+            })
+            .collect()
+    }
+}
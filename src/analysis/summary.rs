@@ -0,0 +1,317 @@
+//! Summary of a flight, for logbooks and debriefing
+//!
+//! The block times follow the usual logbook convention: out is when the aircraft first moves, off is the takeoff,
+//! on is the landing and in is when the aircraft last stops moving. Altitude and speed statistics cover the rows in
+//! the air, while the acceleration and engine peaks cover the whole log.
+//!
+//! NormAc records the change from 1 G, as the Garmin EIS does, so the normal acceleration is reported as the load
+//! factor by adding 1 G.
+
+use crate::airport::{AirportEvent, AirportVisit};
+use crate::analysis::phase::{FlightPhase, FlightPhases, PhaseClassifier};
+use crate::avionics::AvionicsLog;
+use crate::data::{column_f64, elapsed_seconds, integrate, timestamps};
+use crate::geo::{positions, Position};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The number of rows averaged to read the fuel quantity at the start and end of the log, as fuel sloshes in the
+/// tanks
+const FUEL_QUANTITY_ROWS: usize = 30;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlightSummary {
+    pub tail_number: Option<String>,
+    pub log_start: Option<DateTime<Utc>>,
+    pub log_end: Option<DateTime<Utc>>,
+    pub out_time: Option<DateTime<Utc>>,
+    pub off_time: Option<DateTime<Utc>>,
+    pub on_time: Option<DateTime<Utc>>,
+    pub in_time: Option<DateTime<Utc>>,
+    /// Hours from out to in
+    pub block_hours: Option<f64>,
+    /// Hours from off to on
+    pub flight_hours: Option<f64>,
+    /// Distance flown in the air, in nautical miles
+    pub distance_nm: f64,
+    /// The position at takeoff
+    pub departure: Option<Position>,
+    /// The identifier of the airport departed from, where the log records it or it was identified
+    pub departure_airport: Option<String>,
+    /// The runway of the takeoff, where it was identified
+    pub departure_runway: Option<String>,
+    /// The position at landing
+    pub arrival: Option<Position>,
+    /// The identifier of the airport landed at, where it was identified
+    pub arrival_airport: Option<String>,
+    /// The runway of the landing, where it was identified
+    pub arrival_runway: Option<String>,
+    /// ft
+    pub max_altitude: Option<f64>,
+    /// ft
+    pub avg_altitude: Option<f64>,
+    /// kt
+    pub max_ground_speed: Option<f64>,
+    /// kt
+    pub avg_ground_speed: Option<f64>,
+    /// fpm
+    pub max_climb_rate: Option<f64>,
+    /// fpm, as a positive rate
+    pub max_descent_rate: Option<f64>,
+    /// G, the load factor
+    pub max_normal_g: Option<f64>,
+    /// G, the load factor
+    pub min_normal_g: Option<f64>,
+    /// G, in either direction
+    pub max_lateral_g: Option<f64>,
+    /// lbs, the drop in the fuel quantity of both tanks
+    pub fuel_used_quantity: Option<f64>,
+    /// gals, the integrated fuel flow
+    pub fuel_used_flow: Option<f64>,
+    /// The largest value of each engine column
+    pub engine_peaks: BTreeMap<String, f64>,
+}
+
+fn max(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::max)
+}
+
+fn min(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::min)
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn hours(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Option<f64> {
+    Some((to? - from?).num_seconds() as f64 / 3600.0)
+}
+
+/// The total fuel quantity of both tanks, in each row that records both
+fn fuel_quantity(df: &DataFrame) -> Vec<f64> {
+    column_f64(df, "FQtyLlbs")
+        .into_iter()
+        .zip(column_f64(df, "FQtyRlbs"))
+        .filter_map(|(left, right)| Some(left? + right?))
+        .collect()
+}
+
+impl FlightSummary {
+    /// Summarize a log, classifying its phases with the default thresholds
+    pub fn from_log(log: &AvionicsLog) -> Self {
+        let phases = PhaseClassifier::default().classify(&log.data);
+        Self {
+            tail_number: log.tail_number.clone(),
+            departure_airport: log.departure_airport.clone(),
+            arrival_airport: log.arrival_airport.clone(),
+            ..Self::from_data(&log.data, &phases)
+        }
+    }
+
+    /// Add the airports and runways of the first takeoff and the last landing that were identified
+    pub fn with_airports(mut self, visits: &[AirportVisit]) -> Self {
+        if let Some(takeoff) = visits.iter().find(|v| v.event == AirportEvent::Takeoff) {
+            self.departure_airport = Some(takeoff.airport.clone());
+            self.departure_runway = takeoff.runway.clone();
+        }
+        if let Some(landing) = visits.iter().rev().find(|v| v.event == AirportEvent::Landing) {
+            self.arrival_airport = Some(landing.airport.clone());
+            self.arrival_runway = landing.runway.clone();
+        }
+        self
+    }
+
+    /// Summarize flight data, with the phases classified from it
+    pub fn from_data(df: &DataFrame, phases: &FlightPhases) -> Self {
+        let timestamps = timestamps(df);
+        let positions = positions(df);
+        let airborne = phases.phases.iter().map(|p| p.is_airborne()).collect::<Vec<_>>();
+        // the values of a column in the rows in the air
+        let in_air = |name: &str| {
+            column_f64(df, name)
+                .into_iter()
+                .zip(&airborne)
+                .filter_map(|(v, &air)| v.filter(|_| air))
+                .collect::<Vec<_>>()
+        };
+
+        let segment_start = |phase: FlightPhase| phases.segments.iter().find(|s| s.phase == phase).map(|s| s.start);
+        let out_row = segment_start(FlightPhase::TaxiOut);
+        let off_row = phases.takeoffs().first().copied();
+        let on_row = phases.landings().last().copied();
+        // the aircraft is in once it stops moving after the last landing
+        let moving_speed = PhaseClassifier::default().moving_speed;
+        let in_row = column_f64(df, "GndSpd")
+            .iter()
+            .rposition(|gs| gs.is_some_and(|gs| gs > moving_speed))
+            .filter(|&row| on_row.is_some_and(|on| row >= on))
+            .map(|row| (row + 1).min(df.height() - 1));
+        let time_at = |row: Option<usize>| timestamps[row?];
+        let (out_time, off_time, on_time, in_time) =
+            (time_at(out_row), time_at(off_row), time_at(on_row), time_at(in_row));
+
+        let distance_nm = positions
+            .windows(2)
+            .zip(airborne.windows(2))
+            .filter(|(_, air)| air[0] && air[1])
+            .filter_map(|(p, _)| Some(p[0]?.distance_nm(&p[1]?)))
+            .fold(0.0, |total, d| total + d);
+
+        let vertical_speed = in_air("VSpd");
+        let normal = column_f64(df, "NormAc")
+            .into_iter()
+            .flatten()
+            .map(|g| g + 1.0)
+            .collect::<Vec<_>>();
+        let fuel = fuel_quantity(df);
+        let fuel_used_quantity = (fuel.len() >= 2).then(|| {
+            let rows = FUEL_QUANTITY_ROWS.min(fuel.len() / 2);
+            mean(fuel[..rows].iter().copied()).unwrap() - mean(fuel[fuel.len() - rows..].iter().copied()).unwrap()
+        });
+        let fuel_used_flow = integrate(&column_f64(df, "E1 FFlow"), &elapsed_seconds(df)).map(|v| v / 3600.0);
+
+        let engine_peaks = df
+            .get_column_names()
+            .into_iter()
+            .filter(|name| name.starts_with("E1 "))
+            .filter_map(|name| Some((name.to_string(), max(column_f64(df, name).into_iter().flatten())?)))
+            .collect();
+
+        Self {
+            tail_number: None,
+            log_start: timestamps.iter().flatten().next().copied(),
+            log_end: timestamps.iter().flatten().last().copied(),
+            out_time,
+            off_time,
+            on_time,
+            in_time,
+            block_hours: hours(out_time, in_time),
+            flight_hours: hours(off_time, on_time),
+            distance_nm,
+            departure: off_row.and_then(|row| positions[row]),
+            departure_airport: None,
+            departure_runway: None,
+            arrival: on_row.and_then(|row| positions[row]),
+            arrival_airport: None,
+            arrival_runway: None,
+            max_altitude: max(in_air("AltB").into_iter()),
+            avg_altitude: mean(in_air("AltB").into_iter()),
+            max_ground_speed: max(in_air("GndSpd").into_iter()),
+            avg_ground_speed: mean(in_air("GndSpd").into_iter()),
+            max_climb_rate: max(vertical_speed.iter().copied()).filter(|&v| v > 0.0),
+            max_descent_rate: min(vertical_speed.iter().copied()).filter(|&v| v < 0.0).map(|v| -v),
+            max_normal_g: max(normal.iter().copied()),
+            min_normal_g: min(normal.iter().copied()),
+            max_lateral_g: max(column_f64(df, "LatAc").into_iter().flatten().map(f64::abs)),
+            fuel_used_quantity,
+            fuel_used_flow,
+            engine_peaks,
+        }
+    }
+}
+
+/// An optional value, formatted with its unit, or a dash when unknown
+struct Value<'a, T>(Option<T>, &'a str);
+
+impl<T: fmt::Display> fmt::Display for Value<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(value) => write!(f, "{}{}", value, self.1),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+fn time(t: Option<DateTime<Utc>>) -> Value<'static, String> {
+    Value(t.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()), " UTC")
+}
+
+fn decimal(v: Option<f64>, precision: usize, unit: &str) -> Value<'_, String> {
+    Value(v.map(|v| format!("{:.*}", precision, v)), unit)
+}
+
+fn position(p: Option<Position>) -> Value<'static, String> {
+    Value(p.map(|p| format!("{:.5}, {:.5}", p.latitude, p.longitude)), "")
+}
+
+/// An airport and runway, where known, and a position
+fn place(airport: &Option<String>, runway: &Option<String>, p: Option<Position>) -> String {
+    let position = position(p).to_string();
+    match (airport, runway) {
+        (Some(airport), Some(runway)) => format!("{} runway {} at {}", airport, runway, position),
+        (Some(airport), None) => format!("{} at {}", airport, position),
+        _ => position,
+    }
+}
+
+impl fmt::Display for FlightSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Tail number        {}", Value(self.tail_number.as_deref(), ""))?;
+        writeln!(
+            f,
+            "Log                {} to {}",
+            time(self.log_start),
+            time(self.log_end)
+        )?;
+        writeln!(f, "Out                {}", time(self.out_time))?;
+        writeln!(f, "Off                {}", time(self.off_time))?;
+        writeln!(f, "On                 {}", time(self.on_time))?;
+        writeln!(f, "In                 {}", time(self.in_time))?;
+        writeln!(f, "Block time         {}", decimal(self.block_hours, 1, " h"))?;
+        writeln!(f, "Flight time        {}", decimal(self.flight_hours, 1, " h"))?;
+        writeln!(f, "Distance           {:.1} nm", self.distance_nm)?;
+        writeln!(
+            f,
+            "Departure          {}",
+            place(&self.departure_airport, &self.departure_runway, self.departure)
+        )?;
+        writeln!(
+            f,
+            "Arrival            {}",
+            place(&self.arrival_airport, &self.arrival_runway, self.arrival)
+        )?;
+        writeln!(
+            f,
+            "Altitude           max {}, avg {}",
+            decimal(self.max_altitude, 0, " ft"),
+            decimal(self.avg_altitude, 0, " ft")
+        )?;
+        writeln!(
+            f,
+            "Ground speed       max {}, avg {}",
+            decimal(self.max_ground_speed, 0, " kt"),
+            decimal(self.avg_ground_speed, 0, " kt")
+        )?;
+        writeln!(
+            f,
+            "Vertical speed     climb {}, descent {}",
+            decimal(self.max_climb_rate, 0, " fpm"),
+            decimal(self.max_descent_rate, 0, " fpm")
+        )?;
+        writeln!(
+            f,
+            "Acceleration       normal {} to {}, lateral {}",
+            decimal(self.min_normal_g, 2, " G"),
+            decimal(self.max_normal_g, 2, " G"),
+            decimal(self.max_lateral_g, 2, " G")
+        )?;
+        writeln!(
+            f,
+            "Fuel used          {} by quantity, {} by flow",
+            decimal(self.fuel_used_quantity, 1, " lbs"),
+            decimal(self.fuel_used_flow, 1, " gals")
+        )?;
+        if !self.engine_peaks.is_empty() {
+            writeln!(f, "Engine peaks")?;
+            for (name, value) in &self.engine_peaks {
+                writeln!(f, "  {:<16} {:.1}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
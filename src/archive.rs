@@ -0,0 +1,204 @@
+//! Avionics logs in zip archives and directories
+//!
+//! Logs are pulled off the SD card of the avionics as a folder of files, such as the data_log folder of a Garmin
+//! display, and are often zipped to be sent on. The logs in an archive or directory are found by detecting the format
+//! of each file, and other files are skipped. Each log is named by its path inside the archive or directory, from
+//! which output names are made.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::avionics::{AvionicsLog, AvionicsLogFormat};
+use crate::garmin::{GarminEISLog, LogCheckAction};
+
+/// Where a log in an archive or directory is kept
+#[derive(Debug, Clone)]
+enum LogLocation {
+    File(PathBuf),
+    Zip {
+        archive: PathBuf,
+        index: usize,
+        /// The time the entry was last modified, which a GDL90 capture is dated by
+        modified: Option<NaiveDateTime>,
+    },
+}
+
+/// A log found in a zip archive or a directory
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The path of the log inside the archive or directory
+    pub name: PathBuf,
+    pub format: AvionicsLogFormat,
+    location: LogLocation,
+}
+
+impl LogEntry {
+    /// A log file on its own, named by its file name. If the format is not recognized, returns None.
+    pub fn from_file(path: &Path) -> std::io::Result<Option<Self>> {
+        Ok(sniff_file(path)?.map(|format| LogEntry {
+            name: path.file_name().map_or_else(|| path.to_path_buf(), PathBuf::from),
+            format,
+            location: LogLocation::File(path.to_path_buf()),
+        }))
+    }
+
+    /// Read the log
+    pub fn read(&self) -> Result<AvionicsLog, String> {
+        match &self.location {
+            LogLocation::File(path) => self.format.source(path.clone()).read(),
+            LogLocation::Zip {
+                archive,
+                index,
+                modified,
+            } => {
+                let bytes = read_zip_entry(archive, *index)
+                    .map_err(|e| format!("Error reading {}: {}", self.name.display(), e))?;
+                match (self.format, modified) {
+                    (AvionicsLogFormat::Garmin, _) => {
+                        GarminEISLog::from_reader(bytes.as_slice(), LogCheckAction::default())
+                            .map(|log| AvionicsLog::from_garmin_file(log, &self.name))
+                            .map_err(|e| format!("Error reading Garmin data file: {}", e))
+                    }
+                    (format, _) => format.read_bytes(bytes, modified.map(|t| t.and_utc())),
+                }
+            }
+        }
+    }
+
+    /// The path of an output made from the log, keeping its place in the archive or directory
+    pub fn output_name(&self, extension: &str) -> PathBuf {
+        self.name.with_extension(extension)
+    }
+}
+
+/// Check whether a file is a zip archive
+pub fn is_zip(path: &Path) -> std::io::Result<bool> {
+    let mut magic = [0; 4];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == b"PK\x03\x04" || &magic == b"PK\x05\x06"),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check whether a path holds several logs, being a directory or a zip archive
+pub fn is_collection(path: &Path) -> std::io::Result<bool> {
+    Ok(path.is_dir() || is_zip(path)?)
+}
+
+/// Find the logs in a directory and its subdirectories, or in a zip archive, in the order of their names, with the
+/// errors of the files that could not be read, which are skipped
+pub fn find_logs(path: &Path) -> std::io::Result<(Vec<LogEntry>, Vec<String>)> {
+    let mut logs = Vec::new();
+    let mut errors = Vec::new();
+    if path.is_dir() {
+        find_logs_in_dir(path, path, &mut logs, &mut errors)?;
+    } else {
+        find_logs_in_zip(path, &mut logs, &mut errors)?;
+    }
+    logs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok((logs, errors))
+}
+
+/// Detect the format of a file from its start
+fn sniff_file(path: &Path) -> std::io::Result<Option<AvionicsLogFormat>> {
+    let mut bytes = Vec::new();
+    File::open(path)?
+        .take(AvionicsLogFormat::SNIFF_BYTES as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(AvionicsLogFormat::detect(&bytes))
+}
+
+fn find_logs_in_dir(
+    root: &Path,
+    dir: &Path,
+    logs: &mut Vec<LogEntry>,
+    errors: &mut Vec<String>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        // skip hidden files, such as those left by the operating system on SD cards
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            find_logs_in_dir(root, &path, logs, errors)?;
+            continue;
+        }
+        match sniff_file(&path) {
+            Ok(Some(format)) => logs.push(LogEntry {
+                name: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+                format,
+                location: LogLocation::File(path),
+            }),
+            Ok(None) => {}
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    Ok(())
+}
+
+fn find_logs_in_zip(path: &Path, logs: &mut Vec<LogEntry>, errors: &mut Vec<String>) -> std::io::Result<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    for index in 0..archive.len() {
+        let mut file = match archive.by_index(index) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(format!("{}: entry {}: {}", path.display(), index, e));
+                continue;
+            }
+        };
+        // entries with unsafe paths, such as ones going up out of the archive, are skipped
+        let Some(name) = file.enclosed_name() else {
+            continue;
+        };
+        let hidden = name
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.') || c.as_os_str() == "__MACOSX");
+        if file.is_dir() || hidden {
+            continue;
+        }
+        // the times of zip entries have no time zone, and are taken as UTC
+        let modified = file.last_modified().and_then(|t| {
+            let date = NaiveDate::from_ymd_opt(t.year().into(), t.month().into(), t.day().into())?;
+            let time = NaiveTime::from_hms_opt(t.hour().into(), t.minute().into(), t.second().into())?;
+            Some(NaiveDateTime::new(date, time))
+        });
+        let mut bytes = Vec::new();
+        if let Err(e) = (&mut file)
+            .take(AvionicsLogFormat::SNIFF_BYTES as u64)
+            .read_to_end(&mut bytes)
+        {
+            errors.push(format!("{}: {}", path.join(&name).display(), e));
+            continue;
+        }
+        if let Some(format) = AvionicsLogFormat::detect(&bytes) {
+            logs.push(LogEntry {
+                name,
+                format,
+                location: LogLocation::Zip {
+                    archive: path.to_path_buf(),
+                    index,
+                    modified,
+                },
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Read an entry of a zip archive
+fn read_zip_entry(archive: &Path, index: usize) -> std::io::Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(File::open(archive)?)?;
+    let mut file = archive.by_index(index)?;
+    // the size recorded in the archive is not trusted to allocate by, as the archive may be corrupt or crafted
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
@@ -0,0 +1,215 @@
+//! Reader for Avidyne Entegra / IFD flight data logs
+//!
+//! A log starts with a title line naming Avidyne, followed by `key: value` metadata lines (such as the aircraft
+//! registration and the unit software) and then a CSV table. The first column of the table is the UTC date and the
+//! second the UTC time. Other columns carry their unit in parentheses after their name, for example
+//! `Oil Temp (F)`, spelled in any case (`(GPH)`, `(°F)`). Columns without a unit are recorded in the canonical unit of
+//! the flight data column they map to, and columns in a unit that cannot be converted are skipped, with a warning.
+//!
+//! The table is read with the [`crate::mapping`] reader, using a mapping built from the header of the log.
+
+use crate::mapping::{ColumnMapping, CsvMapping, MappedCsvLog, TimestampMapping};
+use crate::units::normalize_unit;
+use polars::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, ErrorKind};
+use std::path::Path;
+
+const DATE_COLUMN: &str = "Date";
+const TIME_COLUMN: &str = "Time";
+const TIMESTAMP_FORMAT: &str = "%Y/%m/%d %H:%M:%S";
+
+/// The number of lines searched for the table header, after which the file is not considered an Avidyne log
+const MAX_HEADER_LINES: usize = 32;
+
+/// The flight data column of an Avidyne column name (without its unit)
+fn flight_data_column(name: &str) -> Option<String> {
+    let column = match name {
+        "Latitude" => "Latitude",
+        "Longitude" => "Longitude",
+        "Altitude" => "AltB",
+        "Pressure Alt" => "AltPress",
+        "GPS Alt" => "AltGPS",
+        "Baro" => "BaroA",
+        "IAS" => "IAS",
+        "TAS" => "TAS",
+        "GS" => "GndSpd",
+        "VSI" => "VSpd",
+        "Pitch" => "Pitch",
+        "Roll" => "Roll",
+        "Heading" => "HDG",
+        "Track" => "TRK",
+        "OAT" => "OAT",
+        "Lat Accel" => "LatAc",
+        "Norm Accel" => "NormAc",
+        "Wind Speed" => "WndSpd",
+        "Wind Dir" => "WndDr",
+        "Volts" => "bus1volts",
+        "Amps" => "alt1amps",
+        "Fuel Qty L" => "FQtyL",
+        "Fuel Qty R" => "FQtyR",
+        "RPM" => "E1 RPM",
+        "MAP" => "E1 MAP",
+        "% Power" => "E1 %Pwr",
+        "Fuel Flow" => "E1 FFlow",
+        "Fuel Press" => "E1 FPres",
+        "Oil Temp" => "E1 OilT",
+        "Oil Press" => "E1 OilP",
+        "TIT" => "E1 TIT1",
+        // numbered cylinders, such as CHT1 .. CHT6
+        cylinder if cylinder.len() > 3 && (cylinder.starts_with("CHT") || cylinder.starts_with("EGT")) => {
+            let number = &cylinder[3..];
+            if number.parse::<u8>().is_err() {
+                return None;
+            }
+            return Some(format!("E1 {}", cylinder));
+        }
+        _ => return None,
+    };
+    Some(column.to_string())
+}
+
+/// A column of the data table
+#[derive(Debug)]
+pub struct AvidyneColumn {
+    /// The name as it appears in the file, including any unit
+    pub raw_name: String,
+    /// The name without the unit
+    pub name: String,
+    pub unit: Option<String>,
+}
+
+impl AvidyneColumn {
+    fn parse(raw_name: &str) -> Self {
+        let raw_name = raw_name.trim();
+        match raw_name.strip_suffix(')').and_then(|s| s.rsplit_once(" (")) {
+            Some((name, unit)) => Self {
+                raw_name: raw_name.to_string(),
+                name: name.trim().to_string(),
+                unit: Some(unit.trim().to_string()),
+            },
+            None => Self {
+                raw_name: raw_name.to_string(),
+                name: raw_name.to_string(),
+                unit: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AvidyneLogHeader {
+    pub metadata: HashMap<String, String>,
+    pub columns: Vec<AvidyneColumn>,
+    /// The number of lines before the table header
+    pub table_offset: usize,
+}
+
+impl AvidyneLogHeader {
+    pub fn from_csv(path: &Path) -> Result<Self, std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, std::io::Error> {
+        let mut lines = reader.lines();
+        let title = lines
+            .next()
+            .unwrap_or_else(|| Err(std::io::Error::new(ErrorKind::UnexpectedEof, "No lines in file")))?;
+        if !title.contains("Avidyne") {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, "Missing Avidyne title line"));
+        }
+
+        // metadata lines continue until the table header, which starts with the date column
+        let mut metadata = HashMap::new();
+        for (offset, line) in lines.enumerate().take(MAX_HEADER_LINES) {
+            let line = line?;
+            if line.starts_with(&format!("{},", DATE_COLUMN)) {
+                let columns = line.split(',').map(AvidyneColumn::parse).collect::<Vec<_>>();
+                if columns.get(1).map(|c| c.name.as_str()) != Some(TIME_COLUMN) {
+                    return Err(std::io::Error::new(ErrorKind::InvalidData, "Missing time column"));
+                }
+                return Ok(Self {
+                    metadata,
+                    columns,
+                    table_offset: offset + 1,
+                });
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                metadata.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        Err(std::io::Error::new(ErrorKind::InvalidData, "No data table found"))
+    }
+
+    /// The aircraft registration recorded in the log, if any
+    pub fn tail_number(&self) -> Option<&str> {
+        self.metadata.get("Aircraft").map(|s| s.as_str())
+    }
+
+    /// Build the mapping that reads the data table of the log into flight data columns
+    pub fn build_mapping(&self) -> CsvMapping {
+        let columns = self
+            .columns
+            .iter()
+            .filter_map(|column| {
+                flight_data_column(&column.name).map(|field| ColumnMapping {
+                    source: column.raw_name.clone(),
+                    field,
+                    unit: column.unit.as_deref().map(normalize_unit),
+                })
+            })
+            .collect();
+
+        CsvMapping {
+            skip_rows: self.table_offset,
+            skip_rows_after_header: 0,
+            delimiter: ',',
+            timestamp: TimestampMapping {
+                column: None,
+                date_column: Some(self.columns[0].raw_name.clone()),
+                time_column: Some(self.columns[1].raw_name.clone()),
+                format: TIMESTAMP_FORMAT.to_string(),
+                timezone: None,
+            },
+            defaults: BTreeMap::new(),
+            columns,
+        }
+    }
+}
+
+pub struct AvidyneLog {
+    pub header: AvidyneLogHeader,
+    pub data: DataFrame,
+    /// The columns that were skipped, as their unit could not be converted
+    pub warnings: Vec<String>,
+}
+
+impl AvidyneLog {
+    pub fn from_csv(path: &Path) -> PolarsResult<Self> {
+        let header = AvidyneLogHeader::from_csv(path)?;
+        let mut mapping = header.build_mapping();
+        let warnings = mapping.remove_unconvertible_columns();
+        mapping.validate().map_err(|e| polars_err!(ComputeError: "{}", e))?;
+        let data = MappedCsvLog::from_csv(path, &mapping)?.data;
+        Ok(Self {
+            header,
+            data,
+            warnings,
+        })
+    }
+
+    /// Read a log held in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> PolarsResult<Self> {
+        let header = AvidyneLogHeader::from_reader(bytes.as_slice())?;
+        let mut mapping = header.build_mapping();
+        let warnings = mapping.remove_unconvertible_columns();
+        mapping.validate().map_err(|e| polars_err!(ComputeError: "{}", e))?;
+        let data = MappedCsvLog::from_bytes(bytes, &mapping)?.data;
+        Ok(Self {
+            header,
+            data,
+            warnings,
+        })
+    }
+}
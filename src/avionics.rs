@@ -1,62 +1,297 @@
-//! Detects the type of an avionics log file's type
+//! Detects the type of an avionics log file's type, from a file or from bytes in memory
 
-use std::io::ErrorKind;
+use chrono::{DateTime, Utc};
+use polars::prelude::DataFrame;
+use std::io::{ErrorKind, Read};
 use std::path::{Path, PathBuf};
 
-use crate::fdr::FDRFileVersion4;
-use crate::garmin::{GarminEISLog, GarminEISLogHeader, GarminToFDRBuilder};
+use crate::airport::{AirportDatabase, AirportEvent, AirportLocator, AirportVisit};
+use crate::analysis::phase::PhaseClassifier;
+use crate::avidyne::{AvidyneLog, AvidyneLogHeader};
+use crate::ei::{EIEngineLog, EILogHeader};
+use crate::fdr::{CommentField, FDRBuilder, FDRFileVersion4};
+use crate::garmin::{GarminEISLog, GarminEISLogHeader, GarminLogFileName, LogCheckAction};
+use crate::gdl90::Gdl90Log;
+use crate::mapping::{CsvMapping, MappedCsvLog};
+use crate::nmea::NmeaLog;
 
 /// The source of an avionics log
 pub enum AvionicsLogSource {
     /// Log file from a Garmin product, such as the G500 TXi EIS
     Garmin(PathBuf),
+    /// Flight data log from an Avidyne Entegra or IFD system
+    Avidyne(PathBuf),
+    /// Engine monitor log from an Electronics International MVP-50 or CGR-30
+    ElectronicsInternational(PathBuf),
+    /// GDL90 capture recorded from an ADS-B receiver, such as a Stratux
+    Gdl90(PathBuf),
+    /// NMEA 0183 sentence log, such as one recorded by a handheld GPS
+    Nmea(PathBuf),
+    /// CSV file of any layout, read using a column mapping file
+    Csv { path: PathBuf, mapping: PathBuf },
 }
 
-impl AvionicsLogSource {
-    pub fn to_fdr4(&self, aircraft: String, tail_number_override: Option<String>) -> Result<FDRFileVersion4, String> {
+/// A log read from any of the sources, with its data in the flight data columns of the Garmin EIS log
+pub struct AvionicsLog {
+    /// The tail number recorded in the log, if any
+    pub tail_number: Option<String>,
+    pub data: DataFrame,
+    /// The identifier of the airport the log started at, from the name of a Garmin log file, or the airport of the
+    /// first takeoff once identified from an airport database
+    pub departure_airport: Option<String>,
+    /// The identifier of the airport of the last landing, once identified from an airport database
+    pub arrival_airport: Option<String>,
+    /// Problems found while reading the log that did not stop it being read
+    pub warnings: Vec<String>,
+}
+
+impl From<GarminEISLog> for AvionicsLog {
+    fn from(log: GarminEISLog) -> Self {
+        AvionicsLog {
+            tail_number: log.header.metadata.get("tail_number").cloned(),
+            data: log.data,
+            departure_airport: None,
+            arrival_airport: None,
+            warnings: log
+                .log_check
+                .filter(|report| !report.failed.is_empty())
+                .map(|report| report.to_string())
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl From<AvidyneLog> for AvionicsLog {
+    fn from(log: AvidyneLog) -> Self {
+        AvionicsLog {
+            tail_number: log.header.tail_number().map(|t| t.to_string()),
+            data: log.data,
+            departure_airport: None,
+            arrival_airport: None,
+            warnings: log.warnings,
+        }
+    }
+}
+
+impl From<EIEngineLog> for AvionicsLog {
+    fn from(log: EIEngineLog) -> Self {
+        AvionicsLog {
+            tail_number: log.header.tail_number().map(|t| t.to_string()),
+            data: log.data,
+            departure_airport: None,
+            arrival_airport: None,
+            warnings: log.warnings,
+        }
+    }
+}
+
+impl From<DataFrame> for AvionicsLog {
+    /// Data of a source that records no tail number, such as a GDL90 capture, a NMEA log or a mapped CSV file
+    fn from(data: DataFrame) -> Self {
+        AvionicsLog {
+            tail_number: None,
+            data,
+            departure_airport: None,
+            arrival_airport: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl AvionicsLog {
+    /// A Garmin log with the departure airport in the name of its file, warning where the name disagrees with the log
+    pub fn from_garmin_file(log: GarminEISLog, path: &Path) -> Self {
+        let name = GarminLogFileName::parse(path);
+        let disagreements = name.as_ref().map(|name| name.check(&log)).unwrap_or_default();
+        let mut avionics = Self::from(log);
+        avionics.departure_airport = name.and_then(|name| name.airport);
+        avionics.warnings.extend(disagreements);
+        avionics
+    }
+
+    /// Identify the airports of the takeoffs and landings, setting the departure and arrival airports where found
+    pub fn identify_airports(&mut self, airports: &AirportDatabase) -> Vec<AirportVisit> {
+        let phases = PhaseClassifier::default().classify(&self.data);
+        let visits = AirportLocator::default().locate(airports, &self.data, &phases);
+        let first = |event| visits.iter().find(|v| v.event == event).map(|v| v.airport.clone());
+        let last = |event| {
+            visits
+                .iter()
+                .rev()
+                .find(|v| v.event == event)
+                .map(|v| v.airport.clone())
+        };
+        self.departure_airport = first(AirportEvent::Takeoff).or(self.departure_airport.take());
+        self.arrival_airport = last(AirportEvent::Landing).or(self.arrival_airport.take());
+        visits
+    }
+
+    /// Read a log held in memory, such as an upload or an entry of an archive, detecting its format. `ended` is the
+    /// time the recording ended, which a GDL90 capture needs to be dated.
+    pub fn from_bytes(bytes: Vec<u8>, ended: Option<DateTime<Utc>>) -> Result<Self, String> {
+        match AvionicsLogFormat::detect(&bytes) {
+            Some(format) => format.read_bytes(bytes, ended),
+            None => Err("Unable to recognize avionics log format".to_string()),
+        }
+    }
+
+    /// Read a log from any reader, such as stdin, detecting its format
+    pub fn from_reader<R: Read>(mut reader: R, ended: Option<DateTime<Utc>>) -> Result<Self, String> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Error reading log: {}", e))?;
+        Self::from_bytes(bytes, ended)
+    }
+
+    /// Build a FDR file of the flight path and data of the log
+    pub fn to_fdr4(self, aircraft: String, tail_number_override: Option<String>) -> Result<FDRFileVersion4, String> {
         const DEFAULT_TAIL_NUMBER: &str = "N12345";
 
+        // engine monitors record no position, so there is no flight path to replay
+        if self.data.column("Latitude").is_err() || self.data.column("Longitude").is_err() {
+            return Err("The log has no position data to build a flight path from".to_string());
+        }
+
+        let mut builder = FDRBuilder::new(aircraft, DEFAULT_TAIL_NUMBER.to_string());
+
+        if let Some(tail_number) = tail_number_override {
+            builder = builder.with_tail_number_override(tail_number);
+        }
+
+        let mut fdr = builder.build(self.data, self.tail_number);
+        for warning in self.warnings {
+            fdr.add_field(Box::new(CommentField { comment: warning }));
+        }
+        Ok(fdr)
+    }
+}
+
+/// The format of an avionics log, recognized from its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvionicsLogFormat {
+    Garmin,
+    Avidyne,
+    ElectronicsInternational,
+    Gdl90,
+    Nmea,
+}
+
+impl AvionicsLogFormat {
+    /// The bytes at the start of a log that its format is recognized from
+    pub const SNIFF_BYTES: usize = 64 * 1024;
+    /// The bytes at the start of a log that are searched for GDL90 frames and NMEA sentences
+    const SNIFF_MESSAGE_BYTES: usize = 4096;
+
+    /// Detect the format of a log from the bytes at its start. If the format is not recognized, returns None.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        // Each supported format is tried in turn, the first one to recognize the bytes wins
+        if GarminEISLogHeader::from_reader(bytes).is_ok() {
+            return Some(Self::Garmin);
+        }
+        if AvidyneLogHeader::from_reader(bytes).is_ok() {
+            return Some(Self::Avidyne);
+        }
+        if EILogHeader::from_reader(bytes).is_ok() {
+            return Some(Self::ElectronicsInternational);
+        }
+        let messages = &bytes[..bytes.len().min(Self::SNIFF_MESSAGE_BYTES)];
+        if Gdl90Log::is_gdl90(messages) {
+            return Some(Self::Gdl90);
+        }
+        if NmeaLog::is_nmea(&String::from_utf8_lossy(messages)) {
+            return Some(Self::Nmea);
+        }
+        None
+    }
+
+    /// The source reading a file in this format
+    pub fn source(self, path: PathBuf) -> AvionicsLogSource {
         match self {
-            AvionicsLogSource::Garmin(path) => match GarminEISLog::from_csv(&path) {
-                Ok(data) => {
-                    let mut builder = GarminToFDRBuilder::new(aircraft, DEFAULT_TAIL_NUMBER.to_string());
+            Self::Garmin => AvionicsLogSource::Garmin(path),
+            Self::Avidyne => AvionicsLogSource::Avidyne(path),
+            Self::ElectronicsInternational => AvionicsLogSource::ElectronicsInternational(path),
+            Self::Gdl90 => AvionicsLogSource::Gdl90(path),
+            Self::Nmea => AvionicsLogSource::Nmea(path),
+        }
+    }
 
-                    if let Some(tail_number) = tail_number_override {
-                        builder = builder.with_tail_number_override(tail_number);
-                    }
+    /// What a file in this format is called in errors
+    fn description(self) -> &'static str {
+        match self {
+            Self::Garmin => "Garmin data file",
+            Self::Avidyne => "Avidyne data file",
+            Self::ElectronicsInternational => "Electronics International data file",
+            Self::Gdl90 => "GDL90 capture file",
+            Self::Nmea => "NMEA log file",
+        }
+    }
 
-                    Ok(builder.build(data))
-                },
-                Err(e) => Err(format!("Error reading Garmin data file: {}", e)),
+    /// Read a log in this format held in memory. A GDL90 capture has no date, so it is dated by `ended`, the time
+    /// the recording ended, and without it cannot be read.
+    pub fn read_bytes(self, bytes: Vec<u8>, ended: Option<DateTime<Utc>>) -> Result<AvionicsLog, String> {
+        let log = match self {
+            Self::Garmin => {
+                GarminEISLog::from_reader(bytes.as_slice(), LogCheckAction::default()).map(AvionicsLog::from)
+            }
+            Self::Avidyne => AvidyneLog::from_bytes(bytes).map(AvionicsLog::from),
+            Self::ElectronicsInternational => EIEngineLog::from_bytes(bytes).map(AvionicsLog::from),
+            Self::Gdl90 => match ended {
+                Some(end) => Gdl90Log::ending_at(&bytes, end).map(|log| log.data.into()),
+                None => return Err("A GDL90 capture file has no date, and the time it ended is needed".to_string()),
             },
+            Self::Nmea => NmeaLog::from_reader(bytes.as_slice()).map(|log| log.data.into()),
+        };
+        log.map_err(|e| format!("Error reading {}: {}", self.description(), e))
+    }
+}
+
+impl AvionicsLogSource {
+    /// The format of the log, unless it is a mapped CSV file
+    pub fn format(&self) -> Option<AvionicsLogFormat> {
+        match self {
+            AvionicsLogSource::Garmin(_) => Some(AvionicsLogFormat::Garmin),
+            AvionicsLogSource::Avidyne(_) => Some(AvionicsLogFormat::Avidyne),
+            AvionicsLogSource::ElectronicsInternational(_) => Some(AvionicsLogFormat::ElectronicsInternational),
+            AvionicsLogSource::Gdl90(_) => Some(AvionicsLogFormat::Gdl90),
+            AvionicsLogSource::Nmea(_) => Some(AvionicsLogFormat::Nmea),
+            AvionicsLogSource::Csv { .. } => None,
         }
     }
+
+    /// Read the log into the flight data columns
+    pub fn read(&self) -> Result<AvionicsLog, String> {
+        let log = match self {
+            AvionicsLogSource::Garmin(path) => {
+                GarminEISLog::from_csv(path).map(|log| AvionicsLog::from_garmin_file(log, path))
+            }
+            AvionicsLogSource::Avidyne(path) => AvidyneLog::from_csv(path).map(AvionicsLog::from),
+            AvionicsLogSource::ElectronicsInternational(path) => EIEngineLog::from_csv(path).map(AvionicsLog::from),
+            AvionicsLogSource::Gdl90(path) => Gdl90Log::from_file(path).map(|log| log.data.into()),
+            AvionicsLogSource::Nmea(path) => NmeaLog::from_file(path).map(|log| log.data.into()),
+            AvionicsLogSource::Csv { path, mapping } => {
+                let mapping = CsvMapping::from_file(mapping).map_err(|e| format!("Error reading mapping file: {}", e))?;
+                MappedCsvLog::from_csv(path, &mapping).map(|log| log.data.into())
+            }
+        };
+        let description = self.format().map_or("CSV file", AvionicsLogFormat::description);
+        log.map_err(|e| format!("Error reading {}: {}", description, e))
+    }
+
+    pub fn to_fdr4(&self, aircraft: String, tail_number_override: Option<String>) -> Result<FDRFileVersion4, String> {
+        self.read()?.to_fdr4(aircraft, tail_number_override)
+    }
 }
 
 /// Detect the source of an avionics log file. If the source is not recognized, returns None.
 pub fn detect_source(path: &Path) -> Result<Option<AvionicsLogSource>, std::io::Error> {
-    // Currently, only Garmin files are supported.
-    // This is where future detection logic will go.
-    match GarminEISLogHeader::from_csv(path) {
-        Ok(_header) => Ok(Some(AvionicsLogSource::Garmin(path.to_path_buf()))),
-        Err(e)
-            if vec![
-                ErrorKind::NotFound,
-                ErrorKind::PermissionDenied,
-                ErrorKind::IsADirectory,
-                ErrorKind::NotSeekable,
-            ]
-            .contains(&e.kind()) =>
-        {
-            Err(e)
-        }
-        // other remaining errors indicate the format of the file was not recognized
-        Err(e) => {
-            eprintln!(
-                "DEBUG: Error '{:?}' is being disregarded as the format being unrecognized.",
-                e
-            );
-            Ok(None)
-        } // the format is not recognized
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)?
+        .take(AvionicsLogFormat::SNIFF_BYTES as u64)
+        .read_to_end(&mut bytes)?;
+    if bytes.is_empty() {
+        return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "Empty file"));
     }
+    Ok(AvionicsLogFormat::detect(&bytes).map(|format| format.source(path.to_path_buf())))
 }
@@ -0,0 +1,170 @@
+//! Converting many avionics logs at once
+//!
+//! The inputs of a batch are log files, zip archives, directories and glob patterns, which are expanded to the logs
+//! they hold. The output of each log is named by a template of the log's name, date, tail number and departure
+//! airport, so the logs of many flights can be filed by aircraft or by day.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::archive::{find_logs, is_collection, LogEntry};
+use crate::avionics::AvionicsLog;
+use crate::data::first_timestamp;
+
+/// Expand the inputs of a batch to the logs they hold, with the errors of the inputs that could not be read. Files
+/// matched by a glob pattern that are not logs are skipped, as in a directory.
+pub fn find_inputs(inputs: &[PathBuf]) -> (Vec<LogEntry>, Vec<String>) {
+    let mut logs = Vec::new();
+    let mut errors = Vec::new();
+    for input in inputs {
+        let pattern = input.to_string_lossy();
+        if input.exists() || !pattern.contains(['*', '?', '[']) {
+            match find_path(input, true) {
+                Ok((found, unreadable)) => {
+                    logs.extend(found);
+                    errors.extend(unreadable);
+                }
+                Err(e) => errors.push(format!("{}: {}", input.display(), e)),
+            }
+            continue;
+        }
+        let paths = match glob::glob(&pattern) {
+            Ok(paths) => paths,
+            Err(e) => {
+                errors.push(format!("{}: Invalid pattern, {}", pattern, e));
+                continue;
+            }
+        };
+        let mut matched = false;
+        for path in paths {
+            matched = true;
+            match path.map_err(|e| e.to_string()).and_then(|path| find_path(&path, false)) {
+                Ok((found, unreadable)) => {
+                    logs.extend(found);
+                    errors.extend(unreadable);
+                }
+                Err(e) => errors.push(format!("{}: {}", pattern, e)),
+            }
+        }
+        if !matched {
+            errors.push(format!("{}: No files match the pattern", pattern));
+        }
+    }
+    (logs, errors)
+}
+
+/// The logs at a path, with the errors of the files in a directory or archive that could not be read. A file that is
+/// not a log is an error if the file was named rather than matched.
+fn find_path(path: &Path, named: bool) -> Result<(Vec<LogEntry>, Vec<String>), String> {
+    if is_collection(path).map_err(|e| e.to_string())? {
+        let (logs, unreadable) = find_logs(path).map_err(|e| e.to_string())?;
+        if logs.is_empty() && unreadable.is_empty() {
+            return Err("No avionics logs found".to_string());
+        }
+        return Ok((logs, unreadable));
+    }
+    match LogEntry::from_file(path) {
+        Ok(Some(log)) => Ok((vec![log], Vec::new())),
+        Ok(None) if named => Err("Unable to recognize avionics log source".to_string()),
+        Ok(None) => Ok((Vec::new(), Vec::new())),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A template of the names of the outputs of a batch, of the fields:
+/// - `{name}`: the name of the log, with its place in the archive or directory but without its extension
+/// - `{date}`: the UTC date of the first row, as YYYY-MM-DD
+/// - `{time}`: the UTC time of the first row, as HHMMSS
+/// - `{tail}`: the tail number recorded in the log
+/// - `{airport}`: the departure airport, as in the name of a Garmin log or identified from an airport database
+/// - `{arrival}`: the arrival airport, as identified from an airport database
+///
+/// Fields that are not known for a log are written as "unknown".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+}
+
+impl NameTemplate {
+    const FIELDS: [&str; 6] = ["name", "date", "time", "tail", "airport", "arrival"];
+
+    /// Check the fields of a template
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed field in name template: {}", template))?
+                + start;
+            let field = &rest[start + 1..end];
+            if !Self::FIELDS.contains(&field) {
+                return Err(format!(
+                    "Unknown field {{{}}} in name template, expected {{name}}, {{date}}, {{time}}, {{tail}}, {{airport}} or {{arrival}}",
+                    field
+                ));
+            }
+            rest = &rest[end + 1..];
+        }
+        Ok(Self {
+            template: template.to_string(),
+        })
+    }
+
+    /// Whether the names are made from the content of the logs, which must then be read to name their outputs
+    pub fn uses_log(&self) -> bool {
+        Self::FIELDS[1..]
+            .iter()
+            .any(|field| self.template.contains(&format!("{{{}}}", field)))
+    }
+
+    /// The path of the output of a log, relative to the output directory, with the given extension. Without the
+    /// content of the log, only its name is known.
+    pub fn render(&self, entry: &LogEntry, log: Option<&AvionicsLog>, extension: &str) -> PathBuf {
+        let unknown = || "unknown".to_string();
+        let start = log.and_then(|log| first_timestamp(&log.data));
+        let tail = log
+            .and_then(|log| log.tail_number.as_deref())
+            .map(|tail| tail.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_"));
+        let ident = |airport: Option<&str>| {
+            airport.map_or_else(unknown, |airport| {
+                airport.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            })
+        };
+        let mut name = self
+            .template
+            .replace("{name}", &entry.name.with_extension("").to_string_lossy())
+            .replace(
+                "{date}",
+                &start.map_or_else(unknown, |t| t.format("%Y-%m-%d").to_string()),
+            )
+            .replace(
+                "{time}",
+                &start.map_or_else(unknown, |t| t.format("%H%M%S").to_string()),
+            )
+            .replace("{tail}", &tail.unwrap_or_else(unknown))
+            .replace(
+                "{airport}",
+                &ident(log.and_then(|log| log.departure_airport.as_deref())),
+            )
+            .replace("{arrival}", &ident(log.and_then(|log| log.arrival_airport.as_deref())));
+        name.push('.');
+        name.push_str(extension);
+        PathBuf::from(name)
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self {
+            template: "{name}".to_string(),
+        }
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Self::new(template)
+    }
+}
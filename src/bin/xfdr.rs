@@ -5,13 +5,26 @@
 
 use clap::{Parser, ValueEnum};
 use hangar::{
+    airport::{self, AirportDatabase, AirportLocator},
+    analysis::{exceedance, exceedance::AircraftLimits, landing, landing::LandingAnalyzer, phase::PhaseClassifier},
+    archive::{is_collection, LogEntry},
     avionics::{detect_source, AvionicsLogSource},
-    fdr::FDRWriter,
+    batch::{find_inputs, NameTemplate},
+    fdr::{CommentField, FDRFileVersion4, FDRWriter},
+    integrity::{IntegrityAction, IntegrityChecker},
+    quality::{median_filter, QualityChecker},
+    resample::Resampler,
+};
+use rayon::prelude::*;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
 };
-use std::{path::PathBuf, process::ExitCode};
 
 /// Export an X-Plane Flight Data Recorder (FDR) file from an avionics log file.
-/// 
+///
 /// FDR files may be replayed in X-Plane to visualize flight path and telemetry data. This is useful as a post-flight
 /// debriefing and analysis tool, for creating videos, or for sharing flight data with others.
 #[derive(Parser, Debug)]
@@ -25,15 +38,105 @@ struct Args {
     #[arg(short, long, default_value = "Aircraft/Laminar Research/Cirrus SR22/Cirrus SR22.acf")]
     aircraft: String,
 
+    /// Path to a column mapping file, used to read CSV files of any layout. Implies `--source csv`
+    #[arg(short, long, required_if_eq("source", "csv"))]
+    mapping: Option<PathBuf>,
+
     /// Optionally override the aircraft tail number, if any, that was discovered in the avionics log
     #[arg(short, long)]
     tail_number: Option<String>,
 
-    /// Path to an avionics log file
-    input: PathBuf,
+    /// Add a marker at the start of each phase of flight (taxi, takeoff, climb, cruise, ...)
+    #[arg(long)]
+    mark_phases: bool,
+
+    /// Add an event and a marker at each touchdown, with its vertical speed and load factor
+    #[arg(long)]
+    mark_landings: bool,
+
+    /// Path to an aircraft limits file. Adds an event and a marker where engine and electrical limits were exceeded
+    #[arg(short, long)]
+    limits: Option<PathBuf>,
+
+    /// Path to an airports file, as airports.csv of OurAirports. Adds a comment naming the airport of each takeoff and
+    /// landing, and names the airports in the output names of logs written to a directory
+    #[arg(long)]
+    airports: Option<PathBuf>,
+
+    /// Path to a runways file, as runways.csv of OurAirports, to name the runway of each takeoff and landing as well
+    #[arg(long, requires = "airports")]
+    runways: Option<PathBuf>,
+
+    /// What to do with positions without GPS integrity: a poor fix, or protection levels above the alarm limits. By
+    /// default they are dropped, and rows without a position are not written, such as those before the GPS has a fix
+    #[arg(long, value_enum, default_value_t = IntegrityOption::Drop)]
+    gps_integrity: IntegrityOption,
+
+    /// Replace spikes and implausible position jumps with values interpolated from the rows around them
+    #[arg(long)]
+    despike: bool,
+
+    /// Smooth the continuous values with a moving median over a window of rows
+    #[arg(long, value_name = "ROWS")]
+    median_filter: Option<usize>,
+
+    /// Resample the data to a fixed rate, in rows per second, before writing it
+    #[arg(long, value_name = "HZ")]
+    resample: Option<f64>,
+
+    /// Convert any number of inputs at once, in parallel, writing a FDR file of each log to this directory
+    #[arg(short, long, value_name = "DIR")]
+    batch: Option<PathBuf>,
+
+    /// Name the FDR files written to a directory by a template of the fields {name}, {date}, {time}, {tail}, {airport}
+    /// and {arrival}
+    #[arg(long, value_name = "TEMPLATE", default_value = "{name}")]
+    name_template: NameTemplate,
 
-    /// Path to output a FDR file. If not specified, output is written to stdout
-    output: Option<PathBuf>,
+    /// Convert logs whose FDR file already exists in the output directory again, rather than skipping them
+    #[arg(long)]
+    overwrite: bool,
+
+    /// The number of logs converted at once, by default one per CPU core
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Path to an avionics log file, then optionally the path to output a FDR file to, otherwise output is written to
+    /// stdout. A zip archive or directory of log files, such as an SD card's data_log, is converted to a FDR file of
+    /// each log in the output directory. With --batch, every path is an input: a log file, zip archive, directory or
+    /// glob pattern
+    #[arg(required = true, value_name = "PATHS")]
+    paths: Vec<PathBuf>,
+}
+
+impl Args {
+    /// The input, when converting a single input
+    fn input(&self) -> &PathBuf {
+        &self.paths[0]
+    }
+
+    /// The output, when converting a single input
+    fn output(&self) -> Option<&PathBuf> {
+        self.paths.get(1)
+    }
+}
+
+/// What to do with positions without GPS integrity
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum IntegrityOption {
+    /// Leave the positions out of the flight path
+    Drop,
+    /// Keep the positions as recorded
+    Ignore,
+}
+
+impl From<IntegrityOption> for IntegrityAction {
+    fn from(option: IntegrityOption) -> Self {
+        match option {
+            IntegrityOption::Drop => IntegrityAction::Drop,
+            IntegrityOption::Ignore => IntegrityAction::Ignore,
+        }
+    }
 }
 
 /// Supported avionics log sources that can be used as command line arguments
@@ -41,40 +144,305 @@ struct Args {
 enum AviationLogSourceOption {
     /// Flight data logs from Garmin Engine Indication System (EIS) products. One such example is the G500 TXi EIS
     Garmin,
-    // .. add more sources here as they become known (Avidyne, etc.)
+    /// Flight data logs from Avidyne Entegra and IFD systems
+    Avidyne,
+    /// Engine monitor logs from Electronics International MVP-50 and CGR-30 products. These have no flight path
+    ElectronicsInternational,
+    /// GDL90 captures recorded by ADS-B receivers such as Stratux
+    Gdl90,
+    /// NMEA 0183 sentence logs recorded by handheld GPS receivers
+    Nmea,
+    /// CSV files of any layout, read using the column mapping file given by `--mapping`
+    Csv,
+    // .. add more sources here as they become known
 }
 
 impl AviationLogSourceOption {
     /// Create an AvionicsLogSource using the given args
-    fn to_avionics_log_source(&self, args: &Args) -> AvionicsLogSource {
+    fn to_avionics_log_source(self, args: &Args) -> AvionicsLogSource {
         match self {
-            Self::Garmin => AvionicsLogSource::Garmin(args.input.clone()),
+            Self::Garmin => AvionicsLogSource::Garmin(args.input().clone()),
+            Self::Avidyne => AvionicsLogSource::Avidyne(args.input().clone()),
+            Self::ElectronicsInternational => AvionicsLogSource::ElectronicsInternational(args.input().clone()),
+            Self::Gdl90 => AvionicsLogSource::Gdl90(args.input().clone()),
+            Self::Nmea => AvionicsLogSource::Nmea(args.input().clone()),
+            Self::Csv => AvionicsLogSource::Csv {
+                path: args.input().clone(),
+                // clap requires the mapping whenever the csv source is chosen
+                mapping: args.mapping.clone().expect("A mapping file is required for CSV files"),
+            },
             // .. add more sources maps here as they become known
         }
     }
 }
 
+/// Filter the data of the FDR and mark what was found in it, as asked by the args
+fn process(args: &Args, airports: Option<&AirportDatabase>, fdr: &mut FDRFileVersion4) -> Result<(), String> {
+    // leave degraded positions out of the flight path, noting where integrity was lost once the GPS had a fix
+    if args.gps_integrity != IntegrityOption::Ignore {
+        let checker = IntegrityChecker::default();
+        for loss in checker.check(&fdr.data).interruptions() {
+            let comment = format!("GPS integrity lost, {}", loss);
+            fdr.add_field(Box::new(CommentField { comment }));
+        }
+        fdr.data = match checker.apply(fdr.data.clone(), args.gps_integrity.into()) {
+            Ok(data) => data,
+            Err(e) => return Err(format!("GPS integrity error: {}", e)),
+        };
+    }
+
+    // filter glitches out of the data before anything is found in it
+    if args.despike {
+        fdr.data = match QualityChecker::default().despike(&fdr.data) {
+            Ok(data) => data,
+            Err(e) => return Err(format!("Filtering error: {}", e)),
+        };
+    }
+    if let Some(rows) = args.median_filter {
+        fdr.data = match median_filter(&fdr.data, rows) {
+            Ok(data) => data,
+            Err(e) => return Err(format!("Filtering error: {}", e)),
+        };
+    }
+
+    // mark the phases of flight, which X-Plane shows on the timeline during replay
+    if args.mark_phases {
+        let phases = PhaseClassifier::default().classify(&fdr.data);
+        for marker in phases.markers(&fdr.data) {
+            fdr.add_field(Box::new(marker));
+        }
+    }
+
+    // mark the touchdowns
+    if args.mark_landings {
+        let phases = PhaseClassifier::default().classify(&fdr.data);
+        let landings = LandingAnalyzer::default().analyze(&fdr.data, &phases);
+        for field in landing::fdr_fields(&landings, &fdr.data) {
+            fdr.add_field(field);
+        }
+    }
+
+    // mark the exceedances of the aircraft limits
+    if let Some(path) = &args.limits {
+        let limits = match AircraftLimits::from_file(path) {
+            Ok(limits) => limits,
+            Err(e) => return Err(format!("Error reading limits file: {}", e)),
+        };
+        for field in exceedance::fdr_fields(&limits.check(&fdr.data), &fdr.data) {
+            fdr.add_field(field);
+        }
+    }
+
+    // name the airports of the takeoffs and landings
+    if let Some(airports) = airports {
+        let phases = PhaseClassifier::default().classify(&fdr.data);
+        let visits = AirportLocator::default().locate(airports, &fdr.data, &phases);
+        for field in airport::fdr_fields(&visits) {
+            fdr.add_field(field);
+        }
+    }
+
+    // resample last, as the phases and landings are found in the data at its recorded rate
+    if let Some(rate) = args.resample {
+        fdr.data = match Resampler::new(rate)?.resample(&fdr.data) {
+            Ok(data) => data,
+            Err(e) => return Err(format!("Resampling error: {}", e)),
+        };
+    }
+    Ok(())
+}
+
+/// How a log written to a directory was converted
+enum Outcome {
+    Converted(PathBuf),
+    /// The FDR file of the log already exists
+    Skipped(PathBuf),
+}
+
+/// Convert the logs of the inputs in parallel to FDR files in the output directory, and summarize how they went
+fn convert_batch(args: &Args, airports: Option<&AirportDatabase>, inputs: &[PathBuf], output: &Path) -> ExitCode {
+    if args.source.is_some() || args.mapping.is_some() {
+        eprintln!("The source of each log converted to a directory is auto-detected, a source or mapping is not used");
+        return ExitCode::FAILURE;
+    }
+    let pool = match rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or_default())
+        .build()
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Error starting conversions: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (logs, mut failures) = find_inputs(inputs);
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+
+    // the outputs written so far, as a template may give several logs the same name
+    let claimed = Mutex::new(HashSet::new());
+    let outcomes = pool.install(|| {
+        logs.par_iter()
+            .map(|log| {
+                let outcome = convert_entry(args, airports, log, output, &claimed);
+                match &outcome {
+                    Ok(Outcome::Converted(path)) => eprintln!("{} -> {}", log.name.display(), path.display()),
+                    Ok(Outcome::Skipped(path)) => {
+                        eprintln!("{}: skipped, {} exists", log.name.display(), path.display())
+                    }
+                    Err(e) => eprintln!("{}: {}", log.name.display(), e),
+                }
+                outcome
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let converted = outcomes
+        .iter()
+        .filter(|o| matches!(o, Ok(Outcome::Converted(_))))
+        .count();
+    let skipped = outcomes.iter().filter(|o| matches!(o, Ok(Outcome::Skipped(_)))).count();
+    for (log, outcome) in logs.iter().zip(outcomes) {
+        if let Err(e) = outcome {
+            failures.push(format!("{}: {}", log.name.display(), e));
+        }
+    }
+    eprintln!(
+        "{} logs converted, {} skipped, {} failed",
+        converted,
+        skipped,
+        failures.len()
+    );
+    for failure in &failures {
+        eprintln!("  {}", failure);
+    }
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Convert a log to a FDR file in the output directory, unless its FDR file exists
+fn convert_entry(
+    args: &Args,
+    airports: Option<&AirportDatabase>,
+    entry: &LogEntry,
+    output: &Path,
+    claimed: &Mutex<HashSet<PathBuf>>,
+) -> Result<Outcome, String> {
+    // an output is claimed before checking whether it exists, so that of several logs given the same name, one is
+    // converted or skipped and the others fail, whichever order they are converted in
+    let claim = |path: &PathBuf| {
+        if claimed.lock().unwrap().insert(path.clone()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Writing error: {} is the output of another log",
+                path.display()
+            ))
+        }
+    };
+
+    // name the output before reading the log where the template allows, so converted logs are skipped quickly
+    let named = (!args.name_template.uses_log()).then(|| output.join(args.name_template.render(entry, None, "fdr")));
+    if let Some(path) = &named {
+        claim(path)?;
+        if !args.overwrite && path.exists() {
+            return Ok(Outcome::Skipped(path.clone()));
+        }
+    }
+
+    let mut log = entry.read().map_err(|e| format!("Parsing error: {}", e))?;
+    if let Some(airports) = airports {
+        log.identify_airports(airports);
+    }
+    let path = match named {
+        Some(path) => path,
+        None => {
+            let path = output.join(args.name_template.render(entry, Some(&log), "fdr"));
+            claim(&path)?;
+            if !args.overwrite && path.exists() {
+                return Ok(Outcome::Skipped(path));
+            }
+            path
+        }
+    };
+
+    let mut fdr = log
+        .to_fdr4(args.aircraft.clone(), args.tail_number.clone())
+        .map_err(|e| format!("Parsing error: {}", e))?;
+    process(args, airports, &mut fdr)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Writing error: {}", e))?;
+    }
+    fdr.write_fdr(&Some(path.clone()))
+        .map_err(|e| format!("Writing error: {}", e))?;
+    Ok(Outcome::Converted(path))
+}
+
 /// Entrypoint for the xfdr binary
 fn main() -> ExitCode {
     // parse and validate args
     let args = Args::parse();
 
+    // read the airports once, for every log converted
+    let airports = match &args.airports {
+        Some(path) => match AirportDatabase::from_csv(path, args.runways.as_deref()) {
+            Ok(airports) => Some(airports),
+            Err(e) => {
+                eprintln!("Error reading airports file: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    // convert many inputs at once
+    if let Some(output) = &args.batch {
+        return convert_batch(&args, airports.as_ref(), &args.paths, output);
+    }
+    if args.paths.len() > 2 {
+        eprintln!("Expected an input and an output, use --batch to convert more inputs");
+        return ExitCode::FAILURE;
+    }
+
+    // convert each log of a zip archive or directory, errors reading a single file are reported below
+    if is_collection(args.input()).unwrap_or(false) {
+        let Some(output) = args.output() else {
+            eprintln!(
+                "An output directory is required to convert the logs in {}",
+                args.input().display()
+            );
+            return ExitCode::FAILURE;
+        };
+        return convert_batch(&args, airports.as_ref(), std::slice::from_ref(args.input()), output);
+    }
+
+    // a mapping file is only used by the csv source
+    let source_option = match (args.source, &args.mapping) {
+        (None, Some(_)) => Some(AviationLogSourceOption::Csv),
+        (source, _) => source,
+    };
+
     // detect the source of the avionics log file
-    let source = match args.source {
+    let source = match source_option {
         // if the source was specified, map it to the appropriate source
         Some(source) => source.to_avionics_log_source(&args),
         // if the source was not specified, auto-detect it
-        None => match detect_source(&args.input) {
+        None => match detect_source(args.input()) {
             // a source was detected
             Ok(Some(source)) => source,
             // something unknown was detected
             Ok(None) => {
-                eprintln!("Unable to recognize avionics log source: {}", args.input.display());
+                eprintln!("Unable to recognize avionics log source: {}", args.input().display());
                 return ExitCode::FAILURE;
             }
             // input file was not found
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                eprintln!("File not found: {}", args.input.display());
+                eprintln!("File not found: {}", args.input().display());
                 return ExitCode::FAILURE;
             }
             // error occured while detecting the source
@@ -86,7 +454,7 @@ fn main() -> ExitCode {
     };
 
     // parse the source data
-    let fdr = match source.to_fdr4(args.aircraft, args.tail_number) {
+    let mut fdr = match source.to_fdr4(args.aircraft.clone(), args.tail_number.clone()) {
         Ok(fdr) => fdr, // return the parsed data
         Err(e) => {
             eprintln!("Parsing error: {}", e);
@@ -94,14 +462,19 @@ fn main() -> ExitCode {
         }
     };
 
+    if let Err(e) = process(&args, airports.as_ref(), &mut fdr) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
     // write data and exit
-    return match fdr.write_fdr(&args.output) {
+    match fdr.write_fdr(&args.output().cloned()) {
         Ok(_) => ExitCode::SUCCESS,
         // ignore broken pipe erorrs on stdout (as when on linux when piping output to head)
-        Err(ref e) if args.output.is_none() && e.kind() == std::io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(ref e) if args.output().is_none() && e.kind() == std::io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
         Err(e) => {
             eprintln!("Writing error: {}", e);
             ExitCode::FAILURE
         }
-    };
+    }
 }
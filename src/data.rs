@@ -1,3 +1,4 @@
+use chrono::Utc;
 use polars::prelude::*;
 
 // Clean up a raw column name by trimming whitespace
@@ -8,7 +9,7 @@ pub fn clean_column_name(name: &str) -> &str {
 // Clean the column names of a dataframe using clean_column_name
 pub fn strip_column_names(mut df: DataFrame) -> Result<DataFrame, PolarsError> {
     df.set_column_names(
-        &df.get_columns()
+        df.get_columns()
             .iter()
             .map(|s| clean_column_name(s.name()).to_string())
             .collect::<Vec<String>>(),
@@ -63,3 +64,64 @@ pub fn clean_dataframe(mut lazy: DataFrame) -> Result<DataFrame, PolarsError> {
     lazy = remove_empty_rows(lazy)?;
     Ok(lazy)
 }
+
+/// The first non-null value of the "Timestamp" column, if the data has one
+pub fn first_timestamp(df: &DataFrame) -> Option<chrono::DateTime<Utc>> {
+    df.column("Timestamp")
+        .ok()?
+        .datetime()
+        .ok()?
+        .as_datetime_iter()
+        .flatten()
+        .next()
+        .map(|t| t.and_utc())
+}
+
+/// The values of a numeric column as f64. Returns all None values if the column is missing or not numeric.
+pub fn column_f64(df: &DataFrame, name: &str) -> Vec<Option<f64>> {
+    df.column(name)
+        .and_then(|c| c.cast(&DataType::Float64))
+        .and_then(|c| Ok(c.f64()?.into_iter().collect()))
+        .unwrap_or_else(|_| vec![None; df.height()])
+}
+
+/// The values of the "Timestamp" column. Returns all None values if the data has no timestamps.
+pub fn timestamps(df: &DataFrame) -> Vec<Option<chrono::DateTime<Utc>>> {
+    match df.column("Timestamp").and_then(|c| c.datetime().cloned()) {
+        Ok(ts) => ts.as_datetime_iter().map(|t| t.map(|t| t.and_utc())).collect(),
+        Err(_) => vec![None; df.height()],
+    }
+}
+
+/// Seconds since the first timestamp, for each row
+pub fn elapsed_seconds(df: &DataFrame) -> Vec<Option<f64>> {
+    let timestamps = timestamps(df);
+    let first = timestamps.iter().flatten().next().copied();
+    timestamps
+        .iter()
+        .map(|t| Some((*t)? - first?).map(|d| d.num_microseconds().unwrap_or_default() as f64 / 1e6))
+        .collect()
+}
+
+/// The integral of a column over time, in the unit of the column multiplied by seconds, using the trapezoidal rule.
+/// Intervals where either value or time is missing are skipped. Returns None if no interval could be integrated.
+pub fn integrate(values: &[Option<f64>], elapsed: &[Option<f64>]) -> Option<f64> {
+    let areas = values
+        .windows(2)
+        .zip(elapsed.windows(2))
+        .filter_map(|(v, t)| Some((v[0]? + v[1]?) / 2.0 * (t[1]? - t[0]?)))
+        .collect::<Vec<_>>();
+    (!areas.is_empty()).then(|| areas.iter().sum())
+}
+
+/// Centered moving average over a window of rows, ignoring missing values
+pub fn moving_average(values: &[Option<f64>], window: usize) -> Vec<Option<f64>> {
+    let half = window / 2;
+    (0..values.len())
+        .map(|i| {
+            let slice = &values[i.saturating_sub(half)..(i + half + 1).min(values.len())];
+            let known = slice.iter().flatten().collect::<Vec<_>>();
+            (!known.is_empty()).then(|| known.iter().copied().sum::<f64>() / known.len() as f64)
+        })
+        .collect()
+}
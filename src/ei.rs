@@ -0,0 +1,215 @@
+//! Reader for Electronics International (EI) engine monitor logs, from the MVP-50 and CGR-30 families
+//!
+//! A log starts with a title line naming Electronics International, followed by `key: value` metadata lines (such
+//! as the monitor model, the aircraft registration and optionally the `UTC Offset` of the recorded times). Then
+//! comes a CSV table whose header row starts with the date and time columns, followed by a row of units.
+//!
+//! Column names differ slightly between models and software versions (`OIL T` or `OILT`, `CHT 1` or `CHT1`, `M.P.`
+//! or `MAP`), so names are compared without spaces, periods and case. Columns are mapped to the Garmin engine columns
+//! (`E1 CHT1`, `E1 FFlow`, ...) and converted into their units, along with the `Hobbs` and `FltTime` hour meters.
+//! Columns in a unit that cannot be converted are skipped, with a warning.
+//!
+//! The table is read with the [`crate::mapping`] reader, using a mapping built from the header of the log.
+
+use crate::mapping::{ColumnMapping, CsvMapping, MappedCsvLog, TimestampMapping};
+use crate::units::{canonical_unit, normalize_unit};
+use polars::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, ErrorKind};
+use std::path::Path;
+
+const TIMESTAMP_FORMAT: &str = "%m/%d/%y %H:%M:%S";
+
+/// The number of lines searched for the table header, after which the file is not considered an EI log
+const MAX_HEADER_LINES: usize = 32;
+
+/// Compare column names without spaces, periods and case
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// The flight data column of a normalized EI column name
+fn flight_data_column(name: &str) -> Option<String> {
+    let column = match name {
+        "RPM" => "E1 RPM",
+        "MAP" | "MP" => "E1 MAP",
+        "FF" | "FUELFLOW" => "E1 FFlow",
+        "OILT" => "E1 OilT",
+        "OILP" => "E1 OilP",
+        "FUELP" => "E1 FPres",
+        "%HP" | "%PWR" => "E1 %Pwr",
+        "TIT" | "TIT1" => "E1 TIT1",
+        "OAT" => "OAT",
+        "VOLTS" | "VOLTS1" => "bus1volts",
+        "AMPS" | "AMPS1" => "alt1amps",
+        "FUELL" => "FQtyL",
+        "FUELR" => "FQtyR",
+        "HOBBS" => "Hobbs",
+        "FLTTIME" => "FltTime",
+        // numbered cylinders, such as CHT1 .. CHT6
+        cylinder if cylinder.len() > 3 && (cylinder.starts_with("CHT") || cylinder.starts_with("EGT")) => {
+            let number = cylinder[3..].parse::<u8>().ok()?;
+            return Some(format!("E1 {}{}", &cylinder[..3], number));
+        }
+        _ => return None,
+    };
+    Some(column.to_string())
+}
+
+/// A column of the data table
+#[derive(Debug)]
+pub struct EIColumn {
+    pub name: String,
+    pub unit: String,
+}
+
+#[derive(Debug)]
+pub struct EILogHeader {
+    pub metadata: HashMap<String, String>,
+    pub columns: Vec<EIColumn>,
+    /// The number of lines before the table header
+    pub table_offset: usize,
+}
+
+impl EILogHeader {
+    pub fn from_csv(path: &Path) -> Result<Self, std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, std::io::Error> {
+        let mut lines = reader.lines();
+        let mut next_line = |what: &str| {
+            lines
+                .next()
+                .unwrap_or_else(|| Err(std::io::Error::new(ErrorKind::UnexpectedEof, format!("No {} in file", what))))
+        };
+        let title = next_line("title line")?;
+        if !title.contains("Electronics International") {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Missing Electronics International title line",
+            ));
+        }
+
+        // metadata lines continue until the table header, which starts with the date and time columns
+        let mut metadata = HashMap::new();
+        for offset in 1..MAX_HEADER_LINES {
+            let line = next_line("data table")?;
+            let names = line.split(',').map(normalize_name).collect::<Vec<_>>();
+            if names.len() > 2 && names[0] == "DATE" && names[1] == "TIME" {
+                let units_line = next_line("units line")?;
+                let columns = line
+                    .split(',')
+                    .zip(units_line.split(',').chain(std::iter::repeat("")))
+                    .map(|(name, unit)| EIColumn {
+                        name: name.trim().to_string(),
+                        unit: unit.trim().to_string(),
+                    })
+                    .collect();
+                return Ok(Self {
+                    metadata,
+                    columns,
+                    table_offset: offset,
+                });
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                metadata.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        Err(std::io::Error::new(ErrorKind::InvalidData, "No data table found"))
+    }
+
+    /// The monitor model, such as MVP-50P or CGR-30P
+    pub fn model(&self) -> Option<&str> {
+        self.metadata.get("Model").map(|s| s.as_str())
+    }
+
+    /// The aircraft registration recorded in the log, if any
+    pub fn tail_number(&self) -> Option<&str> {
+        self.metadata.get("Aircraft").map(|s| s.as_str())
+    }
+
+    /// Build the mapping that reads the data table of the log into flight data columns
+    pub fn build_mapping(&self) -> CsvMapping {
+        let columns = self
+            .columns
+            .iter()
+            .filter_map(|column| {
+                flight_data_column(&normalize_name(&column.name)).map(|field| ColumnMapping {
+                    source: column.name.clone(),
+                    field,
+                    // units are always given, a column without one is in the canonical unit
+                    unit: Some(column.unit.trim())
+                        .filter(|unit| !unit.is_empty())
+                        .map(normalize_unit),
+                })
+            })
+            .collect();
+
+        CsvMapping {
+            skip_rows: self.table_offset,
+            skip_rows_after_header: 1,
+            delimiter: ',',
+            timestamp: TimestampMapping {
+                column: None,
+                date_column: Some(self.columns[0].name.clone()),
+                time_column: Some(self.columns[1].name.clone()),
+                format: TIMESTAMP_FORMAT.to_string(),
+                // recorded times are local, the offset to UTC is only known if it was recorded
+                timezone: self.metadata.get("UTC Offset").cloned(),
+            },
+            defaults: BTreeMap::new(),
+            columns,
+        }
+    }
+}
+
+/// Engine data read from an EI engine monitor
+pub struct EIEngineLog {
+    pub header: EILogHeader,
+    pub data: DataFrame,
+    /// The columns that were skipped, as their unit could not be converted
+    pub warnings: Vec<String>,
+}
+
+impl EIEngineLog {
+    pub fn from_csv(path: &Path) -> PolarsResult<Self> {
+        let header = EILogHeader::from_csv(path)?;
+        let mut mapping = header.build_mapping();
+        let warnings = mapping.remove_unconvertible_columns();
+        mapping.validate().map_err(|e| polars_err!(ComputeError: "{}", e))?;
+        let data = MappedCsvLog::from_csv(path, &mapping)?.data;
+        Ok(Self {
+            header,
+            data,
+            warnings,
+        })
+    }
+
+    /// Read a log held in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> PolarsResult<Self> {
+        let header = EILogHeader::from_reader(bytes.as_slice())?;
+        let mut mapping = header.build_mapping();
+        let warnings = mapping.remove_unconvertible_columns();
+        mapping.validate().map_err(|e| polars_err!(ComputeError: "{}", e))?;
+        let data = MappedCsvLog::from_bytes(bytes, &mapping)?.data;
+        Ok(Self {
+            header,
+            data,
+            warnings,
+        })
+    }
+
+    /// The unit of each data column, which are the same as those of the Garmin engine columns
+    pub fn units(&self) -> BTreeMap<String, &'static str> {
+        self.data
+            .get_column_names()
+            .iter()
+            .filter_map(|name| canonical_unit(name).map(|unit| (name.to_string(), unit)))
+            .collect()
+    }
+}
@@ -24,7 +24,7 @@
 
 // In version 4 files, omit the DATA fields and instead the raw csv data will be added to the end of the file.
 // In the csv data, the first 7 columns must be:
-//      zulu time (hh:mm:ss), longitude, latitude, altitude (feet),
+//      zulu time (hh:mm:ss, or hh:mm:ss.sss), longitude, latitude, altitude (feet),
 //      magnetic heading (degrees), pitch (degrees), roll (degrees)
 // The remaining columns may be any data you wish to include. Each additional column you provide must be associated
 // with a DREF entry higher up in the file. These DREF entries must be made in the order the columns appear.
@@ -56,6 +56,7 @@
 // DREF, sim/cockpit2/radios/actuators/com1_frequency_hz				100.0		// comment: constant to do the whole mhz-khz-hz-decimal thing
 // DREF, sim/cockpit2/radios/actuators/com2_frequency_hz				100.0		// comment: constant to do the whole mhz-khz-hz-decimal thing
 
+use crate::data::{first_timestamp, timestamps};
 use polars::prelude::*;
 use std::{io::Write, path::PathBuf};
 
@@ -270,17 +271,85 @@ impl FDRField for EventField {
     }
 }
 
+/// Builds a FDR file from a flight DataFrame, such as the data of any of the supported avionics logs
+#[derive(Default)]
+pub struct FDRBuilder {
+    aircraft: String,
+    tail_number_default: String,
+    tail_number_override: Option<String>,
+}
+
+impl FDRBuilder {
+    pub fn new(aircraft: String, tail_number_default: String) -> Self {
+        Self {
+            aircraft,
+            tail_number_default,
+            tail_number_override: None,
+        }
+    }
+
+    pub fn with_tail_number_override(mut self, tail_number: String) -> Self {
+        self.tail_number_override = Some(tail_number);
+        self
+    }
+
+    /// Build the FDR, using the tail number discovered in the log (if any) unless it was overridden
+    pub fn build(self, data: DataFrame, tail_number: Option<String>) -> FDRFileVersion4 {
+        let mut fields: Vec<Box<dyn FDRField>> = vec![
+            Box::new(AircraftField {
+                aircraft: self.aircraft,
+            }),
+            Box::new(TailNumberField {
+                tail_number: self
+                    .tail_number_override
+                    .or(tail_number)
+                    .unwrap_or(self.tail_number_default),
+            }),
+        ];
+
+        // If there is a time point in the data, add the time fields to the FDR
+        if let Some(first_timestamp) = first_timestamp(&data) {
+            let first_time = first_timestamp.format("%H:%M:%S").to_string();
+            let first_date = first_timestamp.format("%m/%d/%Y").to_string();
+            fields.push(Box::new(FlightTimeField { time: first_time }));
+            fields.push(Box::new(FlightDateField { date: first_date }));
+        }
+
+        FDRFileVersion4::new(data, Some(fields))
+    }
+}
+
 pub trait FDRWriter {
     fn serialize_field(&self, field: &dyn FDRField) -> String {
         let mut line = field.field_name().to_string();
         for value in field.field_values() {
-            line.push_str(",");
+            line.push(',');
             line.push_str(&value);
         }
         line
     }
 
-    fn write_fdr(&self, destination: &Option<PathBuf>) -> std::io::Result<()>;
+    /// Write the FDR to any writer
+    fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()>;
+
+    /// Write the FDR to a file, or stdout if no path is provided
+    fn write_fdr(&self, destination: &Option<PathBuf>) -> std::io::Result<()> {
+        let mut writer = get_writer(destination.as_deref())?;
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// The FDR as bytes in memory
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// The FDR as text in memory
+    fn to_fdr_string(&self) -> std::io::Result<String> {
+        String::from_utf8(self.to_bytes()?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
 }
 
 // create an alias for Box<dyn Write>
@@ -294,6 +363,70 @@ fn get_writer(path: Option<&std::path::Path>) -> std::io::Result<Writer> {
     }
 }
 
+/// The zulu times of the rows in the FDR data, and which rows to keep so that the times strictly increase.
+///
+/// Times are written with milliseconds only when the data has fractional seconds. Hours count on past 24 after
+/// midnight of the first day, so a flight through midnight UTC keeps increasing. When the time goes backwards, the
+/// longest run of rows whose times increase is kept, and the other rows are dropped. A row with a bad time, as after
+/// a GPS reset, is dropped rather than the rows around it. When the clock of the avionics is corrected back, the rows
+/// after the correction at times already written are dropped, as of runs as long the one with earlier rows is kept.
+fn fdr_times(timestamps: &[Option<chrono::DateTime<chrono::Utc>>]) -> (Vec<String>, Vec<bool>) {
+    let micros = timestamps
+        .iter()
+        .map(|t| Some((*t)?.timestamp_micros()))
+        .collect::<Vec<_>>();
+    let fractional = micros.iter().flatten().any(|m| m % 1_000_000 != 0);
+    let resolution = if fractional { 1_000 } else { 1_000_000 };
+    let times = micros
+        .into_iter()
+        .map(|m| Some((m? + resolution / 2).div_euclid(resolution) * resolution))
+        .collect::<Vec<_>>();
+
+    // find the longest run going back from the last row, as (row, time) of the earliest row of the run of each length
+    // with the latest time, and the row kept after each row
+    let mut starts: Vec<(usize, i64)> = Vec::new();
+    let mut next = vec![None; times.len()];
+    for (row, time) in times.iter().enumerate().rev() {
+        let Some(time) = *time else {
+            continue;
+        };
+        let length = starts.partition_point(|&(_, start)| start > time);
+        next[row] = length.checked_sub(1).map(|l| starts[l].0);
+        match starts.get_mut(length) {
+            Some(start) => *start = (row, time),
+            None => starts.push((row, time)),
+        }
+    }
+    let mut keep = vec![false; times.len()];
+    let Some(&(start, first)) = starts.last() else {
+        return (vec![String::new(); times.len()], keep);
+    };
+    let mut row = Some(start);
+    while let Some(kept) = row {
+        keep[kept] = true;
+        row = next[kept];
+    }
+    let midnight = first - first.rem_euclid(86_400_000_000);
+
+    let text = times
+        .iter()
+        .zip(&keep)
+        .map(|(time, &keep)| match time.filter(|_| keep) {
+            Some(time) => {
+                let time = time - midnight;
+                let (hours, minutes, seconds) = (time / 3_600_000_000, time / 60_000_000 % 60, time % 60_000_000);
+                if fractional {
+                    format!("{:02}:{:02}:{:06.3}", hours, minutes, seconds as f64 / 1e6)
+                } else {
+                    format!("{:02}:{:02}:{:02}", hours, minutes, seconds / 1_000_000)
+                }
+            }
+            None => String::new(),
+        })
+        .collect();
+    (text, keep)
+}
+
 pub struct FDRFileVersion4 {
     pub fields: Vec<Box<dyn FDRField>>,
     pub data: DataFrame,
@@ -303,11 +436,8 @@ impl FDRFileVersion4 {
     /// Create a new FDR file version 4
     pub fn new(data: DataFrame, fields: Option<Vec<Box<dyn FDRField>>>) -> Self {
         FDRFileVersion4 {
-            fields: match fields {
-                Some(fields) => fields,
-                None => Vec::new(),
-            },
-            data: data,
+            fields: fields.unwrap_or_default(),
+            data,
         }
     }
 
@@ -315,16 +445,27 @@ impl FDRFileVersion4 {
     pub fn add_field(&mut self, field: Box<dyn FDRField>) {
         self.fields.push(field);
     }
-}
 
-impl FDRWriter for FDRFileVersion4 {
-    fn write_fdr(&self, destination: &Option<PathBuf>) -> std::io::Result<()> {
-        // if an output file is specified, create a writer for it, otherwise stdout
-        let mut writer = match get_writer(destination.as_deref()) {
-            Ok(writer) => writer,
-            Err(e) => return Err(e),
-        };
+    /// The data to write, with the required columns first, checking the data can be written before any output
+    fn shape_data(&self) -> std::io::Result<DataFrame> {
+        const REQUIRED_COLS: [&str; 7] = ["Timestamp", "Longitude", "Latitude", "AltB", "HDG", "Pitch", "Roll"];
+
+        let df = self
+            .data
+            .select(REQUIRED_COLS)
+            .map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Missing required columns: {}", e),
+                )
+            })?
+            .drop_nulls::<String>(None)
+            .map_err(|e| std::io::Error::other(format!("Unable to shape the data: {}", e)))?;
+        Ok(df)
+    }
 
+    /// Write the header lines and the shaped data
+    fn write_data(&self, writer: &mut dyn Write, mut df: DataFrame) -> std::io::Result<()> {
         writeln!(writer, "A")?;
         writeln!(writer, "4")?;
 
@@ -332,20 +473,13 @@ impl FDRWriter for FDRFileVersion4 {
             writeln!(writer, "{}", self.serialize_field(&**field))?;
         }
 
-        const REQUIRED_COLS: [&str; 7] = ["Timestamp", "Longitude", "Latitude", "AltB", "HDG", "Pitch", "Roll"];
-
-        let mut df = self
-            .data
-            .select(REQUIRED_COLS)
-            .expect("Missing required columns")
-            .drop_nulls::<String>(None)
-            .expect("Unable to shape the data");
-
-        // convert Timestamp to hh:mm:ss
-        let ts = df.column("Timestamp").unwrap().datetime().unwrap().strftime("%H:%M:%S");
-        if let Ok(ts) = ts {
-            df.with_column(ts).unwrap();
-        }
+        // convert Timestamp to strictly increasing hh:mm:ss
+        let (times, keep) = fdr_times(&timestamps(&df));
+        let shape_error = |e: PolarsError| std::io::Error::other(format!("Unable to shape the data: {}", e));
+        df.with_column(Column::new("Timestamp".into(), times)).map_err(shape_error)?;
+        let mut df = df
+            .filter(&BooleanChunked::from_slice("keep".into(), &keep))
+            .map_err(shape_error)?;
 
         let result = CsvWriter::new(writer).include_header(false).finish(&mut df);
 
@@ -355,7 +489,24 @@ impl FDRWriter for FDRFileVersion4 {
                 error.kind(),
                 msg.map_or_else(|| error.to_string(), |m| m.to_string()),
             )),
-            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())),
+            Err(e) => Err(std::io::Error::other(e.to_string())),
         }
     }
 }
+
+impl FDRWriter for FDRFileVersion4 {
+    fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let df = self.shape_data()?;
+        self.write_data(writer, df)
+    }
+
+    fn write_fdr(&self, destination: &Option<PathBuf>) -> std::io::Result<()> {
+        // check the data can be written before creating any output
+        let df = self.shape_data()?;
+
+        // if an output file is specified, create a writer for it, otherwise stdout
+        let mut writer = get_writer(destination.as_deref())?;
+        self.write_data(&mut writer, df)?;
+        writer.flush()
+    }
+}
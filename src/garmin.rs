@@ -1,16 +1,15 @@
-use crate::data::{clean_column_name, clean_dataframe};
-use chrono::Utc;
+use crate::data::{clean_column_name, clean_dataframe, first_timestamp};
+use chrono::{NaiveDateTime, Utc};
 use polars::prelude::*;
 use std::collections::HashMap;
-use std::io::{BufRead, Read};
+use std::fmt;
+use std::io::{BufRead, ErrorKind};
 
-use crate::fdr::{AircraftField, FDRField, FDRFileVersion4, FlightDateField, FlightTimeField, TailNumberField};
+use crate::fdr::{FDRBuilder, FDRFileVersion4};
 
 #[derive(Default)]
 pub struct GarminToFDRBuilder {
-    aircraft: String,
-    tail_number_default: String,
-    tail_number_override: Option<String>,
+    builder: FDRBuilder,
 }
 
 impl GarminToFDRBuilder {
@@ -19,44 +18,18 @@ impl GarminToFDRBuilder {
         tail_number_default: String,
     ) -> Self {
         Self {
-            aircraft,
-            tail_number_default,
-            tail_number_override: None,
+            builder: FDRBuilder::new(aircraft, tail_number_default),
         }
     }
 
     pub fn with_tail_number_override(mut self, tail_number: String) -> Self {
-        self.tail_number_override = Some(tail_number);
+        self.builder = self.builder.with_tail_number_override(tail_number);
         self
     }
 
     pub fn build(self, log: GarminEISLog) -> FDRFileVersion4 {
-        let mut fields: Vec<Box<dyn FDRField>> = vec![
-            Box::new(AircraftField {
-                aircraft: self.aircraft,
-            }),
-            Box::new(TailNumberField {
-                tail_number: match self.tail_number_override {
-                    Some(tail_number) => tail_number,
-                    None => log
-                        .header
-                        .metadata
-                        .get("tail_number")
-                        .map_or(self.tail_number_default, |v| v.clone()),
-                },
-            }),
-        ];
-
-        // If there is a time point in the data, add the time fields to the FDR
-        if let Some(first_time) = log.first_time() {
-            let first_timestamp = first_time.to_utc();
-            let first_time = first_timestamp.format("%H:%M:%S").to_string();
-            let first_date = first_timestamp.format("%m/%d/%Y").to_string();
-            fields.push(Box::new(FlightTimeField { time: first_time }));
-            fields.push(Box::new(FlightDateField { date: first_date }));
-        }
-
-        FDRFileVersion4::new(log.data, Some(fields))
+        let tail_number = log.header.metadata.get("tail_number").cloned();
+        self.builder.build(log.data, tail_number)
     }
 }
 
@@ -89,11 +62,100 @@ pub struct GarminEISLogHeader {
 pub struct GarminEISLog {
     pub header: GarminEISLogHeader,
     pub data: DataFrame,
+    /// The verification of the LogCheck of each row, if the log has a LogCheck column
+    pub log_check: Option<LogCheckReport>,
+}
+
+/// What to do with rows that fail their LogCheck
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogCheckAction {
+    /// Drop the rows, as their values cannot be trusted
+    #[default]
+    Drop,
+    /// Keep the rows, adding a "LogCheckValid" column that is false for the rows that failed
+    Mark,
+    /// Keep the rows without verifying them
+    Ignore,
+}
+
+/// A row that failed its LogCheck
+#[derive(Debug, Clone)]
+pub struct FailedRecord {
+    /// The line number in the file, from 1
+    pub line: usize,
+    /// The LogCheck recorded in the row, if it could be read
+    pub recorded: Option<u16>,
+    /// The LogCheck computed from the row
+    pub computed: u16,
+}
+
+/// The verification of the LogCheck of each row of a log
+#[derive(Debug, Clone, Default)]
+pub struct LogCheckReport {
+    /// The number of rows checked
+    pub rows: usize,
+    pub failed: Vec<FailedRecord>,
+    /// Whether the failed rows were dropped
+    pub dropped: bool,
+}
+
+impl fmt::Display for LogCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHOWN_LINES: usize = 10;
+        write!(f, "{} of {} rows failed the LogCheck", self.failed.len(), self.rows)?;
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        if self.dropped {
+            write!(f, " and were dropped")?;
+        }
+        let lines = self.failed.iter().take(SHOWN_LINES).map(|r| r.line.to_string());
+        write!(f, ", at lines {}", lines.collect::<Vec<_>>().join(", "))?;
+        if self.failed.len() > SHOWN_LINES {
+            write!(f, " and {} more", self.failed.len() - SHOWN_LINES)?;
+        }
+        Ok(())
+    }
+}
+
+/// The LogCheck of a row: a CRC-16/CCITT-FALSE of the line up to its LogCheck value, including the separator and
+/// padding before it
+pub fn log_check(line: &[u8]) -> u16 {
+    line.iter().fold(0xffff, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Verify the LogCheck at the end of a data line, returning the recorded and computed values if they differ
+fn verify_line(line: &[u8]) -> Option<(Option<u16>, u16)> {
+    let start = line.iter().rposition(|&b| b == b',').map_or(0, |i| i + 1);
+    let padding = line[start..].iter().take_while(|b| b.is_ascii_whitespace()).count();
+    let (checked, value) = line.split_at(start + padding);
+    let recorded = std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| u16::from_str_radix(v.trim(), 16).ok());
+    let computed = log_check(checked);
+    (recorded != Some(computed)).then_some((recorded, computed))
 }
 
 impl GarminEISLogHeader {
     pub fn from_csv(path: &std::path::Path) -> Result<Self, std::io::Error> {
         let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Self, std::io::Error> {
+        Self::read_lines(&mut reader).map(|(header, _)| header)
+    }
+
+    /// Read the header lines from the start of a log, returning the header and the raw line of column names
+    fn read_lines(reader: &mut impl BufRead) -> Result<(Self, String), std::io::Error> {
         let mut metadata = HashMap::new();
         let mut columns = Vec::new();
 
@@ -102,13 +164,27 @@ impl GarminEISLogHeader {
         // row 2 starts with a comment char and has column units separated by commas
         // row 3 lists the column names separated by commas
 
-        let mut lines = std::io::BufReader::new(file).lines();
-        let metadata_line = lines.next().expect("No lines in file")?;
+        let mut lines = reader.lines();
+        let mut next_line = |what: &str| {
+            lines.next().unwrap_or_else(|| {
+                Err(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    format!("No {} in file", what),
+                ))
+            })
+        };
+        let metadata_line = next_line("metadata line")?;
+        if !metadata_line.starts_with('#') {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Missing metadata comment line",
+            ));
+        }
 
-        let units_line = lines.next().expect("No units line in file")?;
+        let units_line = next_line("units line")?;
         let units = units_line.trim_start_matches('#').split(",");
 
-        let names_line = lines.next().expect("No names line in file")?;
+        let names_line = next_line("names line")?;
         let names = names_line.split(',');
 
         for entry in metadata_line.trim_start_matches('#').split(',') {
@@ -120,14 +196,43 @@ impl GarminEISLogHeader {
             }
         }
 
+        // other files start with a comment line too, so the metadata and date and time columns of a log are required
+        if let Some(key) = ["log_version", "airframe_name"]
+            .into_iter()
+            .find(|k| !metadata.contains_key(*k))
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Missing {} in metadata line", key),
+            ));
+        }
+
         for (unit, name) in units.zip(names) {
             columns.push(GarminEISColumn {
                 name: name.to_string(),
                 unit: unit.trim().to_string(),
             });
         }
+        if let Some(name) = ["Lcl Date", "Lcl Time"]
+            .into_iter()
+            .find(|name| !columns.iter().any(|c| c.name() == *name))
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Missing {} column", name),
+            ));
+        }
 
-        Ok(Self { metadata, columns })
+        Ok((Self { metadata, columns }, names_line))
+    }
+
+    /// The local date and time the log started, from the flightstream_header of the metadata, as
+    /// "71D00611C4E8A_20231104_084813"
+    pub fn flightstream_start(&self) -> Option<NaiveDateTime> {
+        let header = self.metadata.get("flightstream_header")?;
+        let mut parts = header.rsplitn(3, '_');
+        let (time, date) = (parts.next()?, parts.next()?);
+        NaiveDateTime::parse_from_str(&format!("{}{}", date, time), "%Y%m%d%H%M%S").ok()
     }
 
     pub fn build_schema(&self) -> Schema {
@@ -181,52 +286,242 @@ impl GarminEISLogHeader {
 }
 
 impl GarminEISLog {
-    fn read_bytes(path: &std::path::Path) -> std::io::Result<std::io::Cursor<Vec<u8>>> {
-        let mut file = std::fs::File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        // remove trailing null bytes from buffer
-        while buffer.last() == Some(&0) {
-            buffer.pop();
+    /// Read a log, dropping the rows that fail their LogCheck
+    pub fn from_csv(path: &std::path::Path) -> PolarsResult<Self> {
+        Self::from_csv_with_log_check(path, LogCheckAction::default())
+    }
+
+    /// Read a log, verifying the LogCheck of each row if the log has a LogCheck column
+    pub fn from_csv_with_log_check(path: &std::path::Path, action: LogCheckAction) -> PolarsResult<Self> {
+        GarminEISLogReader::open(path, action)?.finish()
+    }
+
+    /// Read a log from any reader, such as a file in memory or an entry of an archive
+    pub fn from_reader<R: BufRead>(reader: R, action: LogCheckAction) -> PolarsResult<Self> {
+        GarminEISLogReader::new(reader, action)?.finish()
+    }
+
+    pub fn first_time(&self) -> Option<chrono::DateTime<Utc>> {
+        first_timestamp(&self.data)
+    }
+}
+
+/// The metadata in the name of a Garmin log file, such as log_231104_084813_KPOU.csv: the local date and time the
+/// log started, and the identifier of the airport it started at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GarminLogFileName {
+    /// The local date and time
+    pub start: NaiveDateTime,
+    /// The identifier of the airport, if the avionics knew where the aircraft was
+    pub airport: Option<String>,
+}
+
+impl GarminLogFileName {
+    /// Seconds by which times of the start of the log may differ, as the log is named before its first row
+    const TOLERANCE: i64 = 60;
+
+    /// Parse the name of a log file from its path. If the name is not that of a Garmin log, returns None.
+    pub fn parse(path: &std::path::Path) -> Option<Self> {
+        let stem = path.file_stem()?.to_str()?;
+        let mut parts = stem.strip_prefix("log_")?.splitn(3, '_');
+        let (date, time) = (parts.next()?, parts.next()?);
+        if date.len() != 6 || time.len() != 6 {
+            return None;
         }
-        Ok(std::io::Cursor::new(buffer))
+        let start = NaiveDateTime::parse_from_str(&format!("{}{}", date, time), "%y%m%d%H%M%S").ok()?;
+        let airport = parts
+            .next()
+            .filter(|ident| !ident.is_empty() && ident.chars().all(|c| c.is_ascii_alphanumeric()))
+            .map(|ident| ident.to_ascii_uppercase());
+        Some(Self { start, airport })
     }
 
-    fn read_df(path: &std::path::Path, schema: &Schema) -> PolarsResult<LazyFrame> {
-        // read the file bytes into a buffer
-        const SKIP_ROWS: usize = 2;
-        // read into dataframe
+    /// Check the start in the name against the log, returning where they disagree. The flightstream_header of the
+    /// header records the same local start. The first row is in UTC, so it must differ by a UTC offset, which is a
+    /// whole number of quarter hours.
+    pub fn check(&self, log: &GarminEISLog) -> Vec<String> {
+        const QUARTER_HOUR: i64 = 15 * 60;
+        const MAX_OFFSET: i64 = 14 * 3600;
+
+        let mut disagreements = Vec::new();
+        if let Some(start) = log.header.flightstream_start() {
+            if (start - self.start).num_seconds().abs() > Self::TOLERANCE {
+                disagreements.push(format!(
+                    "The file name starts the log at {}, but the flightstream header at {}",
+                    self.start, start
+                ));
+            }
+        }
+        if let Some(first) = log.first_time() {
+            let offset = (self.start - first.naive_utc()).num_seconds();
+            let residual = offset - (offset as f64 / QUARTER_HOUR as f64).round() as i64 * QUARTER_HOUR;
+            if residual.abs() > Self::TOLERANCE || offset.abs() > MAX_OFFSET + Self::TOLERANCE {
+                disagreements.push(format!(
+                    "The file name starts the log at {} local time, which is not the time of the first row, {} UTC",
+                    self.start,
+                    first.format("%Y-%m-%d %H:%M:%S")
+                ));
+            }
+        }
+        disagreements
+    }
+}
+
+/// Reads a log in a single pass, in batches of rows, so that a long log can be processed without holding all of it
+/// in memory. The LogCheck of each row is verified as it is read.
+pub struct GarminEISLogReader<R: BufRead> {
+    pub header: GarminEISLogHeader,
+    reader: R,
+    schema: Schema,
+    /// The raw line of column names, which starts each batch parsed
+    names_line: String,
+    action: LogCheckAction,
+    log_check: Option<LogCheckReport>,
+    batch_rows: usize,
+    /// The number of lines read, including the header
+    lines: usize,
+    done: bool,
+}
+
+impl GarminEISLogReader<std::io::BufReader<std::fs::File>> {
+    /// Open a log file, reading its header
+    pub fn open(path: &std::path::Path, action: LogCheckAction) -> std::io::Result<Self> {
+        Self::new(std::io::BufReader::new(std::fs::File::open(path)?), action)
+    }
+}
+
+impl<R: BufRead> GarminEISLogReader<R> {
+    /// The number of lines before the data: the metadata, units and column names
+    const HEADER_LINES: usize = 3;
+    /// The rows in each batch, by default
+    pub const BATCH_ROWS: usize = 10_000;
+
+    /// Read the header of a log from the start of a reader
+    pub fn new(mut reader: R, action: LogCheckAction) -> std::io::Result<Self> {
+        let (header, names_line) = GarminEISLogHeader::read_lines(&mut reader)?;
+        let checked = action != LogCheckAction::Ignore && header.columns.last().is_some_and(|c| c.unit() == "crc16");
+        Ok(Self {
+            schema: header.build_schema(),
+            header,
+            reader,
+            names_line,
+            action,
+            log_check: checked.then(|| LogCheckReport {
+                dropped: action == LogCheckAction::Drop,
+                ..Default::default()
+            }),
+            batch_rows: Self::BATCH_ROWS,
+            lines: Self::HEADER_LINES,
+            done: false,
+        })
+    }
+
+    /// Read batches of the given number of rows
+    pub fn with_batch_rows(mut self, rows: usize) -> Self {
+        self.batch_rows = rows.max(1);
+        self
+    }
+
+    /// The verification of the LogCheck of the rows read so far, if the log has a LogCheck column
+    pub fn log_check(&self) -> Option<&LogCheckReport> {
+        self.log_check.as_ref()
+    }
+
+    /// Read the next batch of rows, or None at the end of the log. Blank lines are skipped, as are the rows that fail
+    /// their LogCheck when dropping them.
+    pub fn next_batch(&mut self) -> PolarsResult<Option<DataFrame>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut buffer = Vec::with_capacity(self.names_line.len() + 1);
+        buffer.extend_from_slice(self.names_line.as_bytes());
+        buffer.push(b'\n');
+        let mut valid = Vec::new();
+        let mut line = Vec::new();
+        while valid.len() < self.batch_rows {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.done = true;
+                break;
+            }
+            self.lines += 1;
+            // the log is padded with null bytes after the last line
+            let content = line.trim_ascii_end();
+            let content = &content[..content.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)];
+            if content.trim_ascii().is_empty() {
+                continue;
+            }
+            let failure = self.log_check.as_mut().and_then(|report| {
+                report.rows += 1;
+                let (recorded, computed) = verify_line(content)?;
+                report.failed.push(FailedRecord {
+                    line: self.lines,
+                    recorded,
+                    computed,
+                });
+                Some(report.dropped)
+            });
+            if failure == Some(true) {
+                continue;
+            }
+            valid.push(failure.is_none());
+            buffer.extend_from_slice(content);
+            buffer.push(b'\n');
+        }
+        if valid.is_empty() && self.done {
+            return Ok(None);
+        }
+        self.parse(buffer, valid).map(Some)
+    }
+
+    fn parse(&self, buffer: Vec<u8>, valid: Vec<bool>) -> PolarsResult<DataFrame> {
         let reader = CsvReadOptions::default()
             .with_has_header(true)
-            .with_schema(Some(Arc::new(schema.clone())))
-            .with_skip_rows(SKIP_ROWS)
-            .into_reader_with_file_handle(Self::read_bytes(path)?);
-        Ok(reader.finish()?.lazy())
+            .with_schema(Some(Arc::new(self.schema.clone())))
+            .into_reader_with_file_handle(std::io::Cursor::new(buffer));
+        let mut data = reader.finish()?;
+        if self.action == LogCheckAction::Mark && self.log_check.is_some() {
+            data.with_column(Column::new("LogCheckValid".into(), valid))?;
+        }
+        let data = parse_datetime(data.lazy(), "Lcl Date", "Lcl Time", "UTCOfst", "Timestamp", true)?;
+        clean_dataframe(data.collect()?)
     }
 
-    pub fn from_csv(path: &std::path::Path) -> PolarsResult<Self> {
-        let header = GarminEISLogHeader::from_csv(path)?;
-        let schema = header.build_schema();
-        let data = Self::read_df(path, &schema)?;
-        let data = parse_datetime(data, "Lcl Date", "Lcl Time", "UTCOfst", "Timestamp", true)?;
-        let data = data.collect()?;
-        let data = clean_dataframe(data)?;
-        Ok(Self { header, data })
+    /// Read the rest of the log into a single DataFrame
+    pub fn finish(mut self) -> PolarsResult<GarminEISLog> {
+        let mut data: Option<DataFrame> = None;
+        while let Some(batch) = self.next_batch()? {
+            match data.as_mut() {
+                Some(data) => {
+                    data.vstack_mut(&batch)?;
+                }
+                None => data = Some(batch),
+            }
+        }
+        let data = match data {
+            Some(mut data) => {
+                data.as_single_chunk_par();
+                data
+            }
+            None => {
+                let mut buffer = self.names_line.clone().into_bytes();
+                buffer.push(b'\n');
+                self.parse(buffer, Vec::new())?
+            }
+        };
+        Ok(GarminEISLog {
+            header: self.header,
+            data,
+            log_check: self.log_check,
+        })
     }
+}
 
-    pub fn first_time(&self) -> Option<chrono::DateTime<Utc>> {
-        match self.data.column("Timestamp") {
-            Ok(Column::Series(s)) => Some(
-                s.datetime()
-                    .unwrap()
-                    .as_datetime_iter()
-                    .next()
-                    .unwrap()
-                    .unwrap()
-                    .and_utc(),
-            ),
-            _ => None,
-        }
+impl<R: BufRead> Iterator for GarminEISLogReader<R> {
+    type Item = PolarsResult<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
     }
 }
 
@@ -0,0 +1,525 @@
+//! Decoder for GDL90 data streams, as recorded by Stratux and other ADS-B receivers
+//!
+//! GDL90 is described by the FAA "GDL 90 Data Interface Specification" (560-1058-00 Rev A). Messages are sent in
+//! frames delimited by a flag byte (0x7E). Inside a frame, flag and control-escape bytes (0x7D) are escaped by a
+//! 0x7D followed by the original byte XOR 0x20. Each frame ends with a CRC-16 of the message, sent LSB first.
+//!
+//! A capture file is simply the raw stream of frames. The messages used to build a flight are:
+//! - Heartbeat (0): the UTC time of day, sent once per second. The date is not part of the stream.
+//! - Ownship Report (10): position, pressure altitude, velocities and track of the receiver's own aircraft
+//! - Ownship Geometric Altitude (11): the GPS (geometric) altitude of the own aircraft
+//! - Traffic Report (20): the same layout as the ownship report, for other aircraft
+//! - AHRS (0x65, sub-id 1): the ForeFlight extension carrying attitude, heading and airspeeds
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use polars::prelude::*;
+use std::path::Path;
+
+/// Marks the start and end of each frame
+pub const FLAG_BYTE: u8 = 0x7E;
+/// Marks that the following byte has been escaped
+pub const CONTROL_ESCAPE: u8 = 0x7D;
+
+const HEARTBEAT: u8 = 0;
+const OWNSHIP_REPORT: u8 = 10;
+const OWNSHIP_GEOMETRIC_ALTITUDE: u8 = 11;
+const TRAFFIC_REPORT: u8 = 20;
+const FOREFLIGHT: u8 = 0x65;
+const FOREFLIGHT_AHRS: u8 = 0x01;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// CRC-16 lookup table for the polynomial x^16 + x^12 + x^5 + 1, as defined by the specification
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the GDL90 frame check sequence of a message (message id and payload, unescaped)
+pub fn crc16(message: &[u8]) -> u16 {
+    message
+        .iter()
+        .fold(0u16, |crc, &b| CRC16_TABLE[(crc >> 8) as usize] ^ (crc << 8) ^ b as u16)
+}
+
+/// Wrap a message in a frame, adding the CRC, escaping and flag bytes
+pub fn encode_frame(message: &[u8]) -> Vec<u8> {
+    let crc = crc16(message);
+    let mut frame = vec![FLAG_BYTE];
+    for &b in message.iter().chain(&[(crc & 0xFF) as u8, (crc >> 8) as u8]) {
+        if b == FLAG_BYTE || b == CONTROL_ESCAPE {
+            frame.push(CONTROL_ESCAPE);
+            frame.push(b ^ 0x20);
+        } else {
+            frame.push(b);
+        }
+    }
+    frame.push(FLAG_BYTE);
+    frame
+}
+
+/// Counts of what was found while splitting a stream into frames
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames with a valid CRC
+    pub frames: usize,
+    /// Frames discarded because the CRC did not match
+    pub crc_errors: usize,
+    /// Valid frames whose message is not decoded by this module
+    pub unknown_messages: usize,
+}
+
+/// Split a byte stream into messages, removing framing and escapes and verifying the CRC of each frame.
+///
+/// Bytes before the first flag byte are ignored, since a capture may start in the middle of a frame.
+pub fn unframe(bytes: &[u8], stats: &mut FrameStats) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let start = match bytes.iter().position(|&b| b == FLAG_BYTE) {
+        Some(start) => start,
+        None => return messages,
+    };
+
+    for raw in bytes[start..].split(|&b| b == FLAG_BYTE).filter(|raw| !raw.is_empty()) {
+        let mut frame = Vec::with_capacity(raw.len());
+        let mut escaped = false;
+        for &b in raw {
+            if escaped {
+                frame.push(b ^ 0x20);
+                escaped = false;
+            } else if b == CONTROL_ESCAPE {
+                escaped = true;
+            } else {
+                frame.push(b);
+            }
+        }
+
+        // a frame holds at least a message id and the two CRC bytes
+        if frame.len() < 3 {
+            stats.crc_errors += 1;
+            continue;
+        }
+        let (message, crc) = frame.split_at(frame.len() - 2);
+        if crc16(message) != u16::from_le_bytes([crc[0], crc[1]]) {
+            stats.crc_errors += 1;
+            continue;
+        }
+        stats.frames += 1;
+        messages.push(message.to_vec());
+    }
+
+    messages
+}
+
+/// Heartbeat message, sent once a second
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// The GPS position is valid
+    pub gps_valid: bool,
+    /// The UTC timing is valid
+    pub utc_ok: bool,
+    /// Seconds since 0000Z
+    pub seconds_since_midnight: u32,
+}
+
+/// Ownship and traffic reports share this layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetReport {
+    /// The ICAO (or other) 24 bit participant address
+    pub address: u32,
+    /// degrees, if the position is valid
+    pub latitude: Option<f64>,
+    /// degrees, if the position is valid
+    pub longitude: Option<f64>,
+    /// ft, referenced to 29.92 inHg
+    pub pressure_altitude: Option<f64>,
+    /// the target reports being airborne rather than on the ground
+    pub airborne: bool,
+    /// Navigation Integrity Category
+    pub nic: u8,
+    /// Navigation Accuracy Category for Position
+    pub nacp: u8,
+    /// kt
+    pub horizontal_velocity: Option<f64>,
+    /// fpm
+    pub vertical_velocity: Option<f64>,
+    /// degrees, if the track or heading is valid
+    pub track: Option<f64>,
+    pub emitter_category: u8,
+    pub callsign: String,
+}
+
+/// Ownship Geometric Altitude message
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeometricAltitude {
+    /// ft above the WGS-84 ellipsoid
+    pub altitude: f64,
+    /// vertical figure of merit, meters
+    pub vfom: Option<f64>,
+}
+
+/// ForeFlight AHRS extension message
+#[derive(Debug, Clone, PartialEq)]
+pub struct AhrsReport {
+    /// degrees, right wing down is positive
+    pub roll: Option<f64>,
+    /// degrees, nose up is positive
+    pub pitch: Option<f64>,
+    /// degrees
+    pub heading: Option<f64>,
+    /// the heading is magnetic rather than true
+    pub heading_magnetic: bool,
+    /// kt
+    pub indicated_airspeed: Option<f64>,
+    /// kt
+    pub true_airspeed: Option<f64>,
+}
+
+/// A decoded GDL90 message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gdl90Message {
+    Heartbeat(Heartbeat),
+    OwnshipReport(TargetReport),
+    OwnshipGeometricAltitude(GeometricAltitude),
+    TrafficReport(TargetReport),
+    Ahrs(AhrsReport),
+    /// A message with a valid frame that this module does not decode, with its message id
+    Unknown(u8),
+}
+
+impl Gdl90Message {
+    /// Decode an unframed message (message id followed by its payload). Returns None if the message is truncated.
+    pub fn decode(message: &[u8]) -> Option<Self> {
+        let (&id, payload) = message.split_first()?;
+        match id {
+            HEARTBEAT if payload.len() >= 6 => Some(Self::Heartbeat(Heartbeat {
+                gps_valid: payload[0] & 0x80 != 0,
+                utc_ok: payload[0] & 0x01 != 0,
+                seconds_since_midnight: ((payload[1] as u32 & 0x80) << 9)
+                    | u16::from_le_bytes([payload[2], payload[3]]) as u32,
+            })),
+            OWNSHIP_REPORT if payload.len() >= 27 => Some(Self::OwnshipReport(decode_target_report(payload))),
+            TRAFFIC_REPORT if payload.len() >= 27 => Some(Self::TrafficReport(decode_target_report(payload))),
+            OWNSHIP_GEOMETRIC_ALTITUDE if payload.len() >= 4 => {
+                let vertical_metrics = u16::from_be_bytes([payload[2], payload[3]]) & 0x7FFF;
+                Some(Self::OwnshipGeometricAltitude(GeometricAltitude {
+                    altitude: i16::from_be_bytes([payload[0], payload[1]]) as f64 * 5.0,
+                    vfom: (vertical_metrics != 0x7FFF).then_some(vertical_metrics as f64),
+                }))
+            }
+            FOREFLIGHT if payload.first() == Some(&FOREFLIGHT_AHRS) && payload.len() >= 11 => {
+                let angle = |hi: u8, lo: u8| {
+                    let raw = i16::from_be_bytes([hi, lo]);
+                    (raw != 0x7FFF).then_some(raw as f64 / 10.0)
+                };
+                let speed = |hi: u8, lo: u8| {
+                    let raw = u16::from_be_bytes([hi, lo]);
+                    (raw != 0xFFFF).then_some(raw as f64)
+                };
+                let raw_heading = u16::from_be_bytes([payload[5], payload[6]]);
+                // bits 14-0 hold a signed heading in tenths of a degree
+                let heading = ((raw_heading << 1) as i16 >> 1) as f64 / 10.0;
+                Some(Self::Ahrs(AhrsReport {
+                    roll: angle(payload[1], payload[2]),
+                    pitch: angle(payload[3], payload[4]),
+                    heading: (raw_heading != 0xFFFF).then_some(heading.rem_euclid(360.0)),
+                    heading_magnetic: raw_heading & 0x8000 != 0,
+                    indicated_airspeed: speed(payload[7], payload[8]),
+                    true_airspeed: speed(payload[9], payload[10]),
+                }))
+            }
+            HEARTBEAT | OWNSHIP_REPORT | TRAFFIC_REPORT | OWNSHIP_GEOMETRIC_ALTITUDE => None,
+            _ => Some(Self::Unknown(id)),
+        }
+    }
+}
+
+/// Sign extend a 24 bit value and scale it to degrees
+fn decode_angle24(b: &[u8]) -> f64 {
+    let raw = ((b[0] as i32) << 24 | (b[1] as i32) << 16 | (b[2] as i32) << 8) >> 8;
+    raw as f64 * 180.0 / (1 << 23) as f64
+}
+
+fn decode_target_report(p: &[u8]) -> TargetReport {
+    let latitude = decode_angle24(&p[4..7]);
+    let longitude = decode_angle24(&p[7..10]);
+    let nic = p[12] >> 4;
+    let nacp = p[12] & 0x0F;
+    // a position of exactly 0,0 with no integrity is used to signal that the position is unavailable
+    let position_valid = !(latitude == 0.0 && longitude == 0.0 && nic == 0);
+
+    let altitude = (p[10] as u16) << 4 | (p[11] as u16) >> 4;
+    let misc = p[11] & 0x0F;
+    let horizontal_velocity = (p[13] as u16) << 4 | (p[14] as u16) >> 4;
+    let vertical_velocity = (((p[14] as u16 & 0x0F) << 12 | (p[15] as u16) << 4) as i16) >> 4;
+
+    TargetReport {
+        address: (p[1] as u32) << 16 | (p[2] as u32) << 8 | p[3] as u32,
+        latitude: position_valid.then_some(latitude),
+        longitude: position_valid.then_some(longitude),
+        pressure_altitude: (altitude != 0xFFF).then_some(altitude as f64 * 25.0 - 1000.0),
+        airborne: misc & 0x08 != 0,
+        nic,
+        nacp,
+        horizontal_velocity: (horizontal_velocity != 0xFFF).then_some(horizontal_velocity as f64),
+        vertical_velocity: (vertical_velocity != -2048).then_some(vertical_velocity as f64 * 64.0),
+        track: (misc & 0x03 != 0).then_some(p[16] as f64 * 360.0 / 256.0),
+        emitter_category: p[17],
+        callsign: String::from_utf8_lossy(&p[18..26]).trim().to_string(),
+    }
+}
+
+/// A flight decoded from a GDL90 capture
+pub struct Gdl90Log {
+    /// One row per second of ownship data, using the same column names as the Garmin flight data
+    pub data: DataFrame,
+    /// One row per traffic report received
+    pub traffic: DataFrame,
+    pub stats: FrameStats,
+}
+
+/// The values of one row of ownship data
+#[derive(Default)]
+struct OwnshipRow {
+    timestamp: i64,
+    report: Option<TargetReport>,
+    geometric_altitude: Option<f64>,
+    ahrs: Option<AhrsReport>,
+}
+
+#[derive(Default)]
+struct TrafficColumns {
+    timestamp: Vec<i64>,
+    address: Vec<String>,
+    callsign: Vec<String>,
+    latitude: Vec<Option<f64>>,
+    longitude: Vec<Option<f64>>,
+    altitude: Vec<Option<f64>>,
+    ground_speed: Vec<Option<f64>>,
+    vertical_speed: Vec<Option<f64>>,
+    track: Vec<Option<f64>>,
+    airborne: Vec<bool>,
+    emitter_category: Vec<i64>,
+}
+
+fn timestamp_column(name: &str, micros: Vec<i64>) -> PolarsResult<Column> {
+    Ok(Series::new(name.into(), micros)
+        .cast(&DataType::Datetime(TimeUnit::Microseconds, Some("UTC".into())))?
+        .into())
+}
+
+/// The date of the first heartbeat of a capture whose recording ended at `end`
+fn first_heartbeat_date(messages: &[Vec<u8>], end: DateTime<Utc>) -> NaiveDate {
+    let mut seconds = messages.iter().filter_map(|m| match Gdl90Message::decode(m) {
+        Some(Gdl90Message::Heartbeat(heartbeat)) => Some((heartbeat.seconds_since_midnight % SECONDS_PER_DAY) as i64),
+        _ => None,
+    });
+    let Some(first) = seconds.next() else {
+        return end.date_naive();
+    };
+    // the time of day only goes backwards by a large amount when crossing midnight
+    let (mut last, mut days) = (first, 0);
+    for second in seconds {
+        if second + i64::from(SECONDS_PER_DAY / 2) < last {
+            days += 1;
+        }
+        last = second;
+    }
+    let start = end - TimeDelta::seconds(days * i64::from(SECONDS_PER_DAY) + last - first);
+
+    // the file is written a little after the last heartbeat, so take the date putting the first heartbeat nearest
+    let time = NaiveTime::MIN + TimeDelta::seconds(first);
+    let date = start.date_naive();
+    [date.pred_opt(), Some(date), date.succ_opt()]
+        .into_iter()
+        .flatten()
+        .min_by_key(|day| (day.and_time(time).and_utc() - start).abs())
+        .unwrap_or(date)
+}
+
+impl Gdl90Log {
+    /// Check if the bytes look like the start of a GDL90 capture, that is they hold a valid frame
+    pub fn is_gdl90(bytes: &[u8]) -> bool {
+        let mut stats = FrameStats::default();
+        unframe(bytes, &mut stats)
+            .iter()
+            .filter_map(|m| Gdl90Message::decode(m))
+            .any(|m| !matches!(m, Gdl90Message::Unknown(_)))
+    }
+
+    /// Read a capture file. The capture does not contain the date of the flight, so it is dated by the time the file
+    /// was last modified, at the end of the recording.
+    pub fn from_file(path: &Path) -> PolarsResult<Self> {
+        let bytes = std::fs::read(path)?;
+        let modified: DateTime<Utc> = std::fs::metadata(path)?.modified()?.into();
+        Self::ending_at(&bytes, modified)
+    }
+
+    /// Decode a capture whose recording ended at `end`, dating the first heartbeat by the length of the capture
+    pub fn ending_at(bytes: &[u8], end: DateTime<Utc>) -> PolarsResult<Self> {
+        let mut stats = FrameStats::default();
+        let messages = unframe(bytes, &mut stats);
+        let date = first_heartbeat_date(&messages, end);
+        Self::from_messages(&messages, stats, date)
+    }
+
+    /// Decode a capture, with `date` being the UTC date of the first heartbeat in the capture
+    pub fn from_bytes(bytes: &[u8], date: NaiveDate) -> PolarsResult<Self> {
+        let mut stats = FrameStats::default();
+        let messages = unframe(bytes, &mut stats);
+        Self::from_messages(&messages, stats, date)
+    }
+
+    fn from_messages(messages: &[Vec<u8>], mut stats: FrameStats, date: NaiveDate) -> PolarsResult<Self> {
+        let mut rows: Vec<OwnshipRow> = Vec::new();
+        let mut traffic = TrafficColumns::default();
+        let mut current: Option<OwnshipRow> = None;
+        let mut day = date;
+        let mut last_seconds: Option<u32> = None;
+
+        for message in messages.iter().filter_map(|m| Gdl90Message::decode(m)) {
+            match message {
+                Gdl90Message::Heartbeat(heartbeat) => {
+                    let seconds = heartbeat.seconds_since_midnight % SECONDS_PER_DAY;
+                    // the time of day only goes backwards by a large amount when crossing midnight
+                    if matches!(last_seconds, Some(last) if seconds + SECONDS_PER_DAY / 2 < last) {
+                        day = day.succ_opt().ok_or_else(|| polars_err!(ComputeError: "date out of range"))?;
+                    }
+                    last_seconds = Some(seconds);
+
+                    let time = NaiveTime::MIN + TimeDelta::seconds(seconds as i64);
+                    if let Some(row) = current.take() {
+                        rows.push(row);
+                    }
+                    current = Some(OwnshipRow {
+                        timestamp: day.and_time(time).and_utc().timestamp_micros(),
+                        ..Default::default()
+                    });
+                }
+                // nothing can be timestamped until the first heartbeat is seen
+                Gdl90Message::OwnshipReport(report) => {
+                    if let Some(row) = current.as_mut() {
+                        row.report = Some(report);
+                    }
+                }
+                Gdl90Message::OwnshipGeometricAltitude(altitude) => {
+                    if let Some(row) = current.as_mut() {
+                        row.geometric_altitude = Some(altitude.altitude);
+                    }
+                }
+                Gdl90Message::Ahrs(ahrs) => {
+                    if let Some(row) = current.as_mut() {
+                        row.ahrs = Some(ahrs);
+                    }
+                }
+                Gdl90Message::TrafficReport(report) => {
+                    if let Some(row) = current.as_ref() {
+                        traffic.timestamp.push(row.timestamp);
+                        traffic.address.push(format!("{:06X}", report.address));
+                        traffic.callsign.push(report.callsign);
+                        traffic.latitude.push(report.latitude);
+                        traffic.longitude.push(report.longitude);
+                        traffic.altitude.push(report.pressure_altitude);
+                        traffic.ground_speed.push(report.horizontal_velocity);
+                        traffic.vertical_speed.push(report.vertical_velocity);
+                        traffic.track.push(report.track);
+                        traffic.airborne.push(report.airborne);
+                        traffic.emitter_category.push(report.emitter_category as i64);
+                    }
+                }
+                Gdl90Message::Unknown(_) => stats.unknown_messages += 1,
+            }
+        }
+        rows.extend(current);
+
+        // only seconds with a valid ownship position make up the flight
+        rows.retain(|row| matches!(&row.report, Some(report) if report.latitude.is_some()));
+
+        Ok(Self {
+            data: Self::build_ownship_frame(&rows)?,
+            traffic: Self::build_traffic_frame(traffic)?,
+            stats,
+        })
+    }
+
+    fn build_ownship_frame(rows: &[OwnshipRow]) -> PolarsResult<DataFrame> {
+        let report = |f: fn(&TargetReport) -> Option<f64>| {
+            rows.iter()
+                .map(|row| row.report.as_ref().and_then(f))
+                .collect::<Vec<_>>()
+        };
+        let ahrs = |f: fn(&AhrsReport) -> Option<f64>| {
+            rows.iter()
+                .map(|row| row.ahrs.as_ref().and_then(f))
+                .collect::<Vec<_>>()
+        };
+        let track = report(|r| r.track);
+
+        // without an AHRS the attitude is unknown, so the aircraft is shown wings level, pointing along its track
+        let heading = ahrs(|a| a.heading)
+            .into_iter()
+            .zip(&track)
+            .map(|(heading, track)| heading.or(*track))
+            .collect::<Vec<_>>();
+        let level = |f: fn(&AhrsReport) -> Option<f64>| {
+            ahrs(f)
+                .into_iter()
+                .map(|v| v.unwrap_or(0.0))
+                .collect::<Vec<_>>()
+        };
+
+        DataFrame::new(vec![
+            Column::new("Latitude".into(), report(|r| r.latitude)),
+            Column::new("Longitude".into(), report(|r| r.longitude)),
+            Column::new("AltB".into(), report(|r| r.pressure_altitude)),
+            Column::new(
+                "AltGPS".into(),
+                rows.iter().map(|row| row.geometric_altitude).collect::<Vec<_>>(),
+            ),
+            Column::new("IAS".into(), ahrs(|a| a.indicated_airspeed)),
+            Column::new("TAS".into(), ahrs(|a| a.true_airspeed)),
+            Column::new("GndSpd".into(), report(|r| r.horizontal_velocity)),
+            Column::new("VSpd".into(), report(|r| r.vertical_velocity)),
+            Column::new("Pitch".into(), level(|a| a.pitch)),
+            Column::new("Roll".into(), level(|a| a.roll)),
+            Column::new("HDG".into(), heading),
+            Column::new("TRK".into(), track),
+            Column::new(
+                "OnGrnd".into(),
+                rows.iter()
+                    .map(|row| row.report.as_ref().map(|r| !r.airborne as i64))
+                    .collect::<Vec<_>>(),
+            ),
+            timestamp_column("Timestamp", rows.iter().map(|row| row.timestamp).collect())?,
+        ])
+    }
+
+    fn build_traffic_frame(traffic: TrafficColumns) -> PolarsResult<DataFrame> {
+        DataFrame::new(vec![
+            Column::new("Address".into(), traffic.address),
+            Column::new("Callsign".into(), traffic.callsign),
+            Column::new("Latitude".into(), traffic.latitude),
+            Column::new("Longitude".into(), traffic.longitude),
+            Column::new("AltB".into(), traffic.altitude),
+            Column::new("GndSpd".into(), traffic.ground_speed),
+            Column::new("VSpd".into(), traffic.vertical_speed),
+            Column::new("TRK".into(), traffic.track),
+            Column::new(
+                "OnGrnd".into(),
+                traffic.airborne.iter().map(|&a| !a as i64).collect::<Vec<_>>(),
+            ),
+            Column::new("EmitterCategory".into(), traffic.emitter_category),
+            timestamp_column("Timestamp", traffic.timestamp)?,
+        ])
+    }
+}
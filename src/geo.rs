@@ -0,0 +1,50 @@
+//! Positions on the earth, and the distances and bearings between them
+//!
+//! Positions are in degrees of latitude and longitude, as in the flight data. The earth is treated as a sphere, which
+//! is accurate to within about half a percent.
+
+use serde::Serialize;
+
+/// Mean radius of the earth in nautical miles
+pub const EARTH_RADIUS_NM: f64 = 3440.065;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Position {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self { latitude, longitude }
+    }
+
+    /// The great circle distance to another position, in nautical miles
+    pub fn distance_nm(&self, other: &Position) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_NM * a.sqrt().asin()
+    }
+
+    /// The initial true bearing of the great circle to another position, in degrees from 0 up to 360
+    pub fn bearing_deg(&self, other: &Position) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let y = dlon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+}
+
+/// The positions of the flight data, for each row. Rows without a latitude or longitude have no position.
+pub fn positions(df: &polars::prelude::DataFrame) -> Vec<Option<Position>> {
+    let latitude = crate::data::column_f64(df, "Latitude");
+    let longitude = crate::data::column_f64(df, "Longitude");
+    latitude
+        .into_iter()
+        .zip(longitude)
+        .map(|(lat, lon)| Some(Position::new(lat?, lon?)))
+        .collect()
+}
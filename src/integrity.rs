@@ -0,0 +1,239 @@
+//! GPS fix quality and integrity
+//!
+//! The Garmin EIS log records the GPS fix (GPSfix: NoSoln, 2D, 3D or 3DDiff), the horizontal and vertical alarm
+//! limits of the phase of flight (HAL, VAL), and the protection levels of the fix (HPLwas, HPLfd, VPLwas), all in
+//! meters. A position has integrity while the fix is good enough and its protection levels are within the alarm
+//! limits. The WAAS HPL is used where it is known, and otherwise the fault detection (RAIM) HPL. Limits that are not
+//! recorded, or are zero, are not checked.
+//!
+//! Positions without integrity are dropped before building a track by clearing their latitude, longitude and GPS
+//! altitude, so replays skip them rather than jumping to a degraded fix.
+
+use crate::data::{column_f64, timestamps};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The quality of a GPS fix, from worst to best
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum FixQuality {
+    NoSolution,
+    Fix2D,
+    Fix3D,
+    /// A 3D fix corrected by SBAS (WAAS) or DGPS
+    Differential,
+}
+
+impl FixQuality {
+    /// The fix quality of a GPSfix value
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        match value {
+            _ if value.ends_with("Diff") => FixQuality::Differential,
+            _ if value.starts_with("3D") => FixQuality::Fix3D,
+            _ if value.starts_with("2D") => FixQuality::Fix2D,
+            _ => FixQuality::NoSolution,
+        }
+    }
+}
+
+/// Why a position has no integrity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityLossReason {
+    /// The fix is worse than the minimum fix
+    Fix,
+    /// The horizontal protection level exceeds the alarm limit
+    Horizontal,
+    /// The vertical protection level exceeds the alarm limit
+    Vertical,
+}
+
+impl fmt::Display for IntegrityLossReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityLossReason::Fix => f.write_str("no fix"),
+            IntegrityLossReason::Horizontal => f.write_str("HPL above HAL"),
+            IntegrityLossReason::Vertical => f.write_str("VPL above VAL"),
+        }
+    }
+}
+
+/// A run of rows without integrity, for the same reason
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityLoss {
+    pub reason: IntegrityLossReason,
+    /// The first row without integrity
+    pub start: usize,
+    /// The row after the last row without integrity
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// The integrity of the positions of a log
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub rows: usize,
+    /// The rows whose position has integrity
+    pub valid_rows: usize,
+    /// The first row whose position has integrity, once the GPS acquired a fix
+    pub first_valid: Option<usize>,
+    pub losses: Vec<IntegrityLoss>,
+}
+
+impl IntegrityReport {
+    /// The losses of integrity after the first position with integrity, leaving out the time to the first fix
+    pub fn interruptions(&self) -> impl Iterator<Item = &IntegrityLoss> {
+        self.losses
+            .iter()
+            .filter(|loss| self.first_valid.is_some_and(|first| loss.start > first))
+    }
+}
+
+impl fmt::Display for IntegrityLoss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        write!(
+            f,
+            "{} from {} to {} ({} rows)",
+            self.reason,
+            time(self.start_time),
+            time(self.end_time),
+            self.end - self.start
+        )
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} of {} rows have GPS integrity", self.valid_rows, self.rows)?;
+        for loss in &self.losses {
+            writeln!(f, "  {}", loss)?;
+        }
+        Ok(())
+    }
+}
+
+/// What to do with positions without integrity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegrityAction {
+    /// Clear the latitude, longitude and GPS altitude of the rows
+    #[default]
+    Drop,
+    /// Keep the positions, adding a "GPSValid" column that is false for the rows without integrity
+    Mark,
+    /// Keep the positions as recorded
+    Ignore,
+}
+
+/// Checks the GPS fix and protection levels of each row
+#[derive(Debug, Clone)]
+pub struct IntegrityChecker {
+    /// The worst fix a position is used with
+    pub min_fix: FixQuality,
+}
+
+impl Default for IntegrityChecker {
+    fn default() -> Self {
+        Self {
+            min_fix: FixQuality::Fix3D,
+        }
+    }
+}
+
+impl IntegrityChecker {
+    /// Why the position of each row has no integrity, or None where it has. Rows that record no fix, as in logs
+    /// without a fix or NMEA logs without a GGA sentence at that time, are assumed to have integrity.
+    pub fn losses(&self, df: &DataFrame) -> Vec<Option<IntegrityLossReason>> {
+        let n = df.height();
+        let fix = match df.column("GPSfix").and_then(|c| c.cast(&DataType::String)) {
+            Ok(column) => column.str().map_or(vec![None; n], |s| {
+                s.into_iter().map(|v| v.map(FixQuality::parse)).collect()
+            }),
+            Err(_) => vec![None; n],
+        };
+        let hal = column_f64(df, "HAL");
+        let val = column_f64(df, "VAL");
+        let hpl = column_f64(df, "HPLwas")
+            .into_iter()
+            .zip(column_f64(df, "HPLfd"))
+            .map(|(was, fd)| was.or(fd))
+            .collect::<Vec<_>>();
+        let vpl = column_f64(df, "VPLwas");
+        let exceeds = |level: &[Option<f64>], limit: &[Option<f64>], row: usize| match (level[row], limit[row]) {
+            (Some(level), Some(limit)) => limit > 0.0 && level > limit,
+            _ => false,
+        };
+
+        (0..n)
+            .map(|row| {
+                if fix[row].is_some_and(|fix| fix < self.min_fix) {
+                    Some(IntegrityLossReason::Fix)
+                } else if exceeds(&hpl, &hal, row) {
+                    Some(IntegrityLossReason::Horizontal)
+                } else if exceeds(&vpl, &val, row) {
+                    Some(IntegrityLossReason::Vertical)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Find the runs of rows without integrity
+    pub fn check(&self, df: &DataFrame) -> IntegrityReport {
+        let losses = self.losses(df);
+        let timestamps = timestamps(df);
+        let mut runs: Vec<IntegrityLoss> = Vec::new();
+        for (row, reason) in losses.iter().enumerate() {
+            let Some(reason) = *reason else {
+                continue;
+            };
+            match runs.last_mut() {
+                Some(run) if run.end == row && run.reason == reason => {
+                    run.end = row + 1;
+                    run.end_time = timestamps[row];
+                }
+                _ => runs.push(IntegrityLoss {
+                    reason,
+                    start: row,
+                    end: row + 1,
+                    start_time: timestamps[row],
+                    end_time: timestamps[row],
+                }),
+            }
+        }
+        IntegrityReport {
+            rows: df.height(),
+            valid_rows: losses.iter().filter(|l| l.is_none()).count(),
+            first_valid: losses.iter().position(|l| l.is_none()),
+            losses: runs,
+        }
+    }
+
+    /// Drop or mark the positions without integrity
+    pub fn apply(&self, mut df: DataFrame, action: IntegrityAction) -> PolarsResult<DataFrame> {
+        let valid = self.losses(&df).iter().map(|l| l.is_none()).collect::<Vec<_>>();
+        match action {
+            IntegrityAction::Drop => {
+                let mask = BooleanChunked::new("".into(), &valid);
+                for name in ["Latitude", "Longitude", "AltGPS"] {
+                    if let Ok(column) = df.column(name) {
+                        let nulls = Column::full_null(name.into(), df.height(), column.dtype());
+                        let cleared = column
+                            .as_materialized_series()
+                            .zip_with(&mask, nulls.as_materialized_series())?;
+                        df.with_column(cleared)?;
+                    }
+                }
+            }
+            IntegrityAction::Mark => {
+                df.with_column(Column::new("GPSValid".into(), valid))?;
+            }
+            IntegrityAction::Ignore => {}
+        }
+        Ok(df)
+    }
+}
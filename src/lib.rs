@@ -1,7 +1,21 @@
+pub mod airport;
+pub mod analysis;
+pub mod archive;
+pub mod avidyne;
 pub mod avionics;
+pub mod batch;
 pub mod data;
+pub mod ei;
 pub mod fdr;
 pub mod garmin;
+pub mod gdl90;
+pub mod geo;
+pub mod integrity;
+pub mod mapping;
+pub mod nmea;
+pub mod quality;
+pub mod resample;
+pub mod units;
 
 #[doc(hidden)]
 pub fn resource_path(filename: &str) -> std::path::PathBuf {
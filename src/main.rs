@@ -1,3 +1,383 @@
-fn main() {
-    println!("Hello, world!");
+//! Analyze avionics log files.
+//!
+//! Each subcommand reads a log from any of the supported sources, detecting the source from the file, and reports on
+//! the flight it recorded.
+
+use clap::{Parser, Subcommand};
+use hangar::{
+    airport::AirportDatabase,
+    analysis::{
+        approach::StabilizedApproachCriteria,
+        cooling::ShockCoolingAnalyzer,
+        exceedance::AircraftLimits,
+        fuel::FuelAnalyzer,
+        landing::LandingAnalyzer,
+        leaning::{LeaningAnalyzer, LeaningSweep},
+        phase::PhaseClassifier,
+        summary::FlightSummary,
+    },
+    avionics::{detect_source, AvionicsLog, AvionicsLogSource},
+    integrity::IntegrityChecker,
+    quality::QualityChecker,
+};
+use polars::prelude::*;
+use std::{path::PathBuf, process::ExitCode};
+
+/// Analyze avionics log files
+#[derive(Parser, Debug)]
+#[command(version, about, long_about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Summarize a flight: block and flight times, distance, altitudes, speeds, accelerations, fuel and engine peaks
+    Summary {
+        /// Path to an airports file, as airports.csv of OurAirports, to identify the departure and arrival airports
+        #[arg(long)]
+        airports: Option<PathBuf>,
+
+        /// Path to a runways file, as runways.csv of OurAirports, to identify the runways as well
+        #[arg(long, requires = "airports")]
+        runways: Option<PathBuf>,
+
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Report where engine and electrical values exceeded the limits of the aircraft
+    Exceedances {
+        /// Path to the aircraft limits file
+        #[arg(short, long)]
+        limits: PathBuf,
+
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Evaluate whether the final approach of each landing was stabilized between two gates
+    Approaches {
+        /// Height above the field, in ft, from which the approach must be stabilized
+        #[arg(long, default_value_t = StabilizedApproachCriteria::default().upper_gate)]
+        upper_gate: f64,
+
+        /// Height above the field, in ft, at which the evaluation ends
+        #[arg(long, default_value_t = StabilizedApproachCriteria::default().lower_gate)]
+        lower_gate: f64,
+
+        /// The target approach speed in kt, otherwise the average airspeed between the gates
+        #[arg(long)]
+        approach_speed: Option<f64>,
+
+        /// Do not check the CDI deflections, as on visual approaches
+        #[arg(long)]
+        visual: bool,
+
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Find where cylinders were shock cooled in the descent, with the power and vertical speed at the time
+    Cooling {
+        /// The CHT rate, in deg F/min, below which a cylinder is shock cooled
+        #[arg(short, long, default_value_t = ShockCoolingAnalyzer::default().threshold, allow_hyphen_values = true)]
+        threshold: f64,
+
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Reconcile the fuel used by the totalizer with the fuel quantity senders, with the fuel burned in each phase
+    Fuel {
+        /// The fraction of the fuel used by flow beyond which the totalizer and senders disagree
+        #[arg(short, long, default_value_t = FuelAnalyzer::default().tolerance)]
+        tolerance: f64,
+
+        /// Gals kept in reserve, which are not counted in the endurance
+        #[arg(short, long, default_value_t = FuelAnalyzer::default().reserve)]
+        reserve: f64,
+
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Measure each landing: touchdown vertical speed, load factor and speed, float, crab and crosswind
+    Landings {
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Check the GPS fix and WAAS integrity of each position, and report the intervals without integrity
+    Integrity {
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Check the data quality: spikes, implausible position jumps, and frozen or stuck sensors
+    Quality {
+        /// Output JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+    /// Find leaning sweeps in cruise and report the peak EGT fuel flow of each cylinder and the GAMI spread
+    Leaning {
+        /// Path to write the fuel flow and EGTs of the sweeps to as CSV, for charting
+        #[arg(short, long)]
+        chart: Option<PathBuf>,
+
+        #[command(flatten)]
+        log: LogArgs,
+    },
+}
+
+/// The avionics log to analyze
+#[derive(clap::Args, Debug)]
+struct LogArgs {
+    /// Path to a column mapping file, used to read CSV files of any layout. Otherwise the source is auto-detected
+    #[arg(short, long)]
+    mapping: Option<PathBuf>,
+
+    /// Path to an avionics log file
+    input: PathBuf,
+}
+
+impl LogArgs {
+    /// Detect the source of the log and read it
+    fn read(&self) -> Result<AvionicsLog, String> {
+        let source = match &self.mapping {
+            Some(mapping) => AvionicsLogSource::Csv {
+                path: self.input.clone(),
+                mapping: mapping.clone(),
+            },
+            None => match detect_source(&self.input) {
+                Ok(Some(source)) => source,
+                Ok(None) => {
+                    return Err(format!(
+                        "Unable to recognize avionics log source: {}",
+                        self.input.display()
+                    ))
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(format!("File not found: {}", self.input.display()))
+                }
+                Err(e) => return Err(format!("Detection error: {}", e)),
+            },
+        };
+        let log = source.read()?;
+        for warning in &log.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        Ok(log)
+    }
+}
+
+/// Write the chart data of each sweep to a CSV file, numbering the sweeps in the "Sweep" column
+fn write_chart(path: &PathBuf, sweeps: &[LeaningSweep], data: &DataFrame) -> PolarsResult<()> {
+    let mut chart = DataFrame::empty();
+    for (i, sweep) in sweeps.iter().enumerate() {
+        let mut rows = sweep.chart_data(data)?;
+        rows.with_column(Column::new("Sweep".into(), vec![i as u32 + 1; rows.height()]))?;
+        chart.vstack_mut(&rows)?;
+    }
+    let mut file = std::fs::File::create(path)?;
+    CsvWriter::new(&mut file).finish(&mut chart)
+}
+
+/// Entrypoint for the hangar binary
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let result = match args.command {
+        Command::Summary {
+            airports,
+            runways,
+            json,
+            log,
+        } => log.read().and_then(|mut log| {
+            let visits = match airports {
+                Some(path) => AirportDatabase::from_csv(&path, runways.as_deref())
+                    .map(|airports| log.identify_airports(&airports))
+                    .map_err(|e| format!("Error reading airports file: {}", e))?,
+                None => Vec::new(),
+            };
+            let summary = FlightSummary::from_log(&log).with_airports(&visits);
+            if json {
+                let text = serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                print!("{}", summary);
+            }
+            Ok(())
+        }),
+        Command::Exceedances { limits, json, log } => AircraftLimits::from_file(&limits)
+            .map_err(|e| format!("Error reading limits file: {}", e))
+            .and_then(|limits| {
+                let exceedances = limits.check(&log.read()?.data);
+                if json {
+                    let text = serde_json::to_string_pretty(&exceedances).map_err(|e| e.to_string())?;
+                    println!("{}", text);
+                } else if exceedances.is_empty() {
+                    println!("No exceedances");
+                } else {
+                    for exceedance in exceedances {
+                        println!("{}", exceedance);
+                    }
+                }
+                Ok(())
+            }),
+        Command::Approaches {
+            upper_gate,
+            lower_gate,
+            approach_speed,
+            visual,
+            json,
+            log,
+        } => log.read().and_then(|log| {
+            let phases = PhaseClassifier::default().classify(&log.data);
+            let mut criteria = StabilizedApproachCriteria {
+                upper_gate,
+                lower_gate,
+                approach_speed,
+                ..Default::default()
+            };
+            if visual {
+                (criteria.max_hcdi, criteria.max_vcdi) = (None, None);
+            }
+            let evaluations = criteria.evaluate(&log.data, &phases);
+            if json {
+                let text = serde_json::to_string_pretty(&evaluations).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else if evaluations.is_empty() {
+                println!("No landings found");
+            } else {
+                for evaluation in evaluations {
+                    print!("{}", evaluation);
+                }
+            }
+            Ok(())
+        }),
+        Command::Cooling { threshold, json, log } => log.read().and_then(|log| {
+            let phases = PhaseClassifier::default().classify(&log.data);
+            let analyzer = ShockCoolingAnalyzer {
+                threshold,
+                ..Default::default()
+            };
+            let reports = analyzer.analyze(&log.data, &phases);
+            if json {
+                let text = serde_json::to_string_pretty(&reports).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else if reports.is_empty() {
+                println!("No flights found");
+            } else {
+                for report in reports {
+                    print!("{}", report);
+                }
+            }
+            Ok(())
+        }),
+        Command::Fuel {
+            tolerance,
+            reserve,
+            json,
+            log,
+        } => log.read().and_then(|log| {
+            let phases = PhaseClassifier::default().classify(&log.data);
+            let analyzer = FuelAnalyzer {
+                tolerance,
+                reserve,
+                ..Default::default()
+            };
+            let report = analyzer.analyze(&log.data, &phases);
+            if json {
+                let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                print!("{}", report);
+            }
+            Ok(())
+        }),
+        Command::Landings { json, log } => log.read().and_then(|log| {
+            let phases = PhaseClassifier::default().classify(&log.data);
+            let landings = LandingAnalyzer::default().analyze(&log.data, &phases);
+            if json {
+                let text = serde_json::to_string_pretty(&landings).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else if landings.is_empty() {
+                println!("No landings found");
+            } else {
+                for landing in landings {
+                    print!("{}", landing);
+                }
+            }
+            Ok(())
+        }),
+        Command::Integrity { json, log } => log.read().and_then(|log| {
+            let report = IntegrityChecker::default().check(&log.data);
+            if json {
+                let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                print!("{}", report);
+            }
+            Ok(())
+        }),
+        Command::Quality { json, log } => log.read().and_then(|log| {
+            let report = QualityChecker::default().check(&log.data);
+            if json {
+                let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                print!("{}", report);
+            }
+            Ok(())
+        }),
+        Command::Leaning { chart, log } => log.read().and_then(|log| {
+            let phases = PhaseClassifier::default().classify(&log.data);
+            let sweeps = LeaningAnalyzer::default().find_sweeps(&log.data, &phases);
+            if sweeps.is_empty() {
+                println!("No leaning sweeps found");
+            }
+            for sweep in &sweeps {
+                print!("{}", sweep);
+            }
+            match chart {
+                Some(path) => write_chart(&path, &sweeps, &log.data).map_err(|e| format!("Writing error: {}", e)),
+                None => Ok(()),
+            }
+        }),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,269 @@
+//! Reader for CSV logs of any layout, driven by a user-supplied mapping file
+//!
+//! The mapping file is TOML and describes how to read a CSV file into the flight data columns. For example:
+//!
+//! ```toml
+//! # rows before the row of column names, such as a title
+//! skip_rows = 1
+//! # rows between the column names and the data, such as a row of units
+//! skip_rows_after_header = 1
+//! delimiter = ","
+//!
+//! [timestamp]
+//! # either a single column holding the date and time...
+//! column = "Time"
+//! # ...or separate columns, which are joined with a space before parsing
+//! # date_column = "Date"
+//! # time_column = "Time"
+//! format = "%Y-%m-%d %H:%M:%S"
+//! # the timezone of the timestamps, when the format has no offset. Either a name from the timezone database or a
+//! # fixed offset such as "-04:00". Defaults to UTC.
+//! timezone = "America/New_York"
+//!
+//! # columns that are missing from the file, filled with a constant
+//! [defaults]
+//! Pitch = 0.0
+//! Roll = 0.0
+//!
+//! [[columns]]
+//! source = "Alt (m)"
+//! field = "AltB"
+//! unit = "m"
+//! ```
+//!
+//! Each of the `columns` maps a column of the file to a flight data column. When a `unit` is given, values are
+//! converted into the canonical unit of the field (see [`crate::units`]).
+
+use crate::data::clean_dataframe;
+use crate::units::{canonical_unit, conversion};
+use polars::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+fn default_delimiter() -> char {
+    ','
+}
+
+/// How to read a CSV file into the flight data columns
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvMapping {
+    /// Rows before the row of column names
+    #[serde(default)]
+    pub skip_rows: usize,
+    /// Rows between the column names and the data
+    #[serde(default)]
+    pub skip_rows_after_header: usize,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    pub timestamp: TimestampMapping,
+    /// Constant values of flight data columns that are missing from the file
+    #[serde(default)]
+    pub defaults: BTreeMap<String, f64>,
+    pub columns: Vec<ColumnMapping>,
+}
+
+/// Where the time of each row is found, and how it is formatted
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimestampMapping {
+    /// A column holding both the date and the time
+    pub column: Option<String>,
+    /// A column holding the date, used along with `time_column`
+    pub date_column: Option<String>,
+    /// A column holding the time, used along with `date_column`
+    pub time_column: Option<String>,
+    /// The chrono format of the timestamp. Separate date and time columns are joined with a space.
+    pub format: String,
+    /// The timezone of the timestamps, when the format has no offset. Either a name from the timezone database or a
+    /// fixed offset such as "-04:00". Defaults to UTC.
+    pub timezone: Option<String>,
+}
+
+/// A column of the file, mapped to a flight data column
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnMapping {
+    /// The column name in the file
+    pub source: String,
+    /// The flight data column name
+    pub field: String,
+    /// The unit of the column in the file, if it needs converting
+    pub unit: Option<String>,
+}
+
+impl CsvMapping {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let mapping: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        mapping.validate()?;
+        Ok(mapping)
+    }
+
+    /// Check that the mapping can be applied, independent of any file
+    pub fn validate(&self) -> Result<(), String> {
+        let timestamp = &self.timestamp;
+        match (&timestamp.column, &timestamp.date_column, &timestamp.time_column) {
+            (Some(_), None, None) | (None, Some(_), Some(_)) => (),
+            _ => return Err("timestamp needs either a column, or both a date_column and a time_column".to_string()),
+        }
+        if !self.delimiter.is_ascii() {
+            return Err(format!("delimiter '{}' must be an ASCII character", self.delimiter));
+        }
+        for column in &self.columns {
+            Self::validate_unit(column)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the unit of a column can be converted into the unit of its field
+    fn validate_unit(column: &ColumnMapping) -> Result<(), String> {
+        if let Some(unit) = &column.unit {
+            let canonical = canonical_unit(&column.field)
+                .ok_or_else(|| format!("field '{}' has no known unit to convert '{}' into", column.field, unit))?;
+            conversion(unit, canonical)
+                .ok_or_else(|| format!("unable to convert '{}' into '{}' for '{}'", unit, canonical, column.field))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the columns whose unit cannot be converted, returning a warning for each. A mapping built from the
+    /// header of a log skips such columns rather than failing to read the log.
+    pub fn remove_unconvertible_columns(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        self.columns.retain(|column| match Self::validate_unit(column) {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("Skipped column '{}': {}", column.source, e));
+                false
+            }
+        });
+        warnings
+    }
+
+    /// The expression building a flight data column from its mapped column
+    fn column_expr(column: &ColumnMapping) -> Expr {
+        let source = col(column.source.as_str());
+        let expr = match canonical_unit(&column.field) {
+            Some("enum") => source,
+            Some("bool") | Some("#") => source.cast(DataType::Float64).cast(DataType::Int64),
+            canonical => {
+                let values = source.cast(DataType::Float64);
+                match (column.unit.as_deref(), canonical) {
+                    (Some(unit), Some(canonical)) => {
+                        // validated when the mapping was loaded
+                        let (scale, offset) = conversion(unit, canonical).unwrap();
+                        values * lit(scale) + lit(offset)
+                    }
+                    _ => values,
+                }
+            }
+        };
+        expr.alias(column.field.as_str())
+    }
+
+    /// The expression building the UTC Timestamp column. Rows whose time cannot be parsed, or falls in the hour skipped
+    /// when the clocks go forward, have no timestamp and are dropped. A time in the hour repeated when the clocks go
+    /// back is taken as the earlier of the two.
+    fn timestamp_expr(&self) -> Expr {
+        let timestamp = &self.timestamp;
+        let text = match (&timestamp.column, &timestamp.date_column, &timestamp.time_column) {
+            (Some(column), _, _) => col(column.as_str()),
+            (None, Some(date), Some(time)) => {
+                concat_str(vec![col(date.as_str()), lit(" "), col(time.as_str())], "", false)
+            }
+            _ => unreachable!("validated when the mapping was loaded"),
+        };
+        let options = |format: &str| StrptimeOptions {
+            format: Some(format.into()),
+            strict: false,
+            ..Default::default()
+        };
+        let fixed_offset = timestamp
+            .timezone
+            .as_deref()
+            .filter(|timezone| timezone.starts_with(['+', '-']));
+
+        let expr = if timestamp.format.contains("%z") {
+            text.str().to_datetime(
+                Some(TimeUnit::Microseconds),
+                Some("UTC".into()),
+                options(&timestamp.format),
+                lit("raise"),
+            )
+        } else if let Some(offset) = fixed_offset {
+            // a fixed offset is parsed as part of the timestamp, like the Garmin UTCOfst column
+            concat_str(vec![text, lit(offset)], "", false).str().to_datetime(
+                Some(TimeUnit::Microseconds),
+                Some("UTC".into()),
+                options(&format!("{}%z", timestamp.format)),
+                lit("raise"),
+            )
+        } else {
+            let local = timestamp.timezone.as_deref().unwrap_or("UTC");
+            text.str()
+                .to_datetime(Some(TimeUnit::Microseconds), None, options(&timestamp.format), lit("raise"))
+                .dt()
+                .replace_time_zone(Some(local.into()), lit("earliest"), NonExistent::Null)
+                .dt()
+                .convert_time_zone("UTC".into())
+        };
+        expr.alias("Timestamp")
+    }
+}
+
+/// A flight read from a CSV file using a mapping
+pub struct MappedCsvLog {
+    pub data: DataFrame,
+}
+
+impl MappedCsvLog {
+    pub fn from_csv(path: &Path, mapping: &CsvMapping) -> PolarsResult<Self> {
+        let data = Self::read_options(mapping)
+            .try_into_reader_with_file_path(Some(path.to_path_buf()))?
+            .finish()?;
+        Self::from_dataframe(clean_dataframe(data)?, mapping)
+    }
+
+    /// Read a CSV file held in memory
+    pub fn from_bytes(bytes: Vec<u8>, mapping: &CsvMapping) -> PolarsResult<Self> {
+        let data = Self::read_options(mapping)
+            .into_reader_with_file_handle(std::io::Cursor::new(bytes))
+            .finish()?;
+        Self::from_dataframe(clean_dataframe(data)?, mapping)
+    }
+
+    fn read_options(mapping: &CsvMapping) -> CsvReadOptions {
+        // every column is read as text, so that the mapping alone decides the types
+        CsvReadOptions::default()
+            .with_has_header(true)
+            .with_skip_rows(mapping.skip_rows)
+            .with_skip_rows_after_header(mapping.skip_rows_after_header)
+            .with_infer_schema_length(Some(0))
+            .map_parse_options(|options| options.with_separator(mapping.delimiter as u8))
+    }
+
+    /// Apply a mapping to a DataFrame of text columns, as read from a CSV file
+    pub fn from_dataframe(data: DataFrame, mapping: &CsvMapping) -> PolarsResult<Self> {
+        let mut columns = mapping.columns.iter().map(CsvMapping::column_expr).collect::<Vec<_>>();
+        columns.extend(
+            mapping
+                .defaults
+                .iter()
+                .map(|(field, value)| lit(*value).alias(field.as_str())),
+        );
+        columns.push(mapping.timestamp_expr());
+
+        let data = data
+            .lazy()
+            .select(columns)
+            .filter(col("Timestamp").is_not_null())
+            .collect()?;
+        Ok(Self { data })
+    }
+}
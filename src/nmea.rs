@@ -0,0 +1,294 @@
+//! Reader for NMEA 0183 sentence logs, such as those recorded by handheld GPS receivers
+//!
+//! Each line of a log holds one sentence in the form `$TTSSS,field1,field2,...*HH` where TT is the talker id (GP,
+//! GN, ...), SSS is the sentence type and HH is the hexadecimal XOR of all characters between `$` and `*`.
+//! Proprietary sentences (such as Garmin's PGRMZ) start with `$P` followed by the manufacturer code.
+//!
+//! The sentences used to build a flight are:
+//! - GGA: time, position, fix quality and altitudes
+//! - RMC: time, date, position, ground speed, track and magnetic variation
+//! - VTG: track and ground speed
+//! - PGRMZ: Garmin barometric altitude
+//! - HDT: true heading, from a compass or AHRS
+//!
+//! Only RMC carries a date. Sentences are grouped into rows by their UTC time, and the date is carried from the RMC
+//! sentences to the other rows, advancing it whenever the time of day rolls over midnight.
+
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike};
+use polars::prelude::*;
+use std::io::BufRead;
+use std::path::Path;
+
+const FEET_PER_METER: f64 = 3.28084;
+const SECONDS_PER_HALF_DAY: u32 = 12 * 60 * 60;
+
+/// Counts of what was found while reading a log
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SentenceStats {
+    /// Sentences that passed checksum validation and were used
+    pub sentences: usize,
+    /// Sentences discarded because the checksum did not match
+    pub checksum_errors: usize,
+    /// Sentences with a valid checksum of a type that is not used
+    pub unsupported: usize,
+}
+
+/// Validate the checksum of a sentence and split it into its fields, with the address field (`GPGGA`) first.
+///
+/// Sentences without a checksum are accepted, since it is optional for some sentences. Returns None if the line is
+/// not a sentence or the checksum does not match.
+pub fn parse_sentence(line: &str) -> Option<Vec<&str>> {
+    let start = line.find('$')?;
+    let sentence = line[start + 1..].trim_end();
+    let body = match sentence.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+            if body.bytes().fold(0, |acc, b| acc ^ b) != expected {
+                return None;
+            }
+            body
+        }
+        None => sentence,
+    };
+    Some(body.split(',').collect())
+}
+
+/// The sentence type of an address field, without the talker id (`GPGGA` -> `GGA`). Proprietary sentences are
+/// returned whole, as are addresses that are not ASCII, such as those of binary files read as text.
+fn sentence_type(address: &str) -> &str {
+    if address.starts_with('P') || address.len() < 5 {
+        address
+    } else {
+        address.get(2..).unwrap_or(address)
+    }
+}
+
+fn parse_time(field: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(field, "%H%M%S%.f").ok()
+}
+
+fn seconds(time: NaiveTime) -> u32 {
+    time.num_seconds_from_midnight()
+}
+
+fn parse_date(field: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(field, "%d%m%y").ok()
+}
+
+fn parse_f64(field: &str) -> Option<f64> {
+    field.parse().ok()
+}
+
+/// Parse a ddmm.mmmm or dddmm.mmmm coordinate with its hemisphere into signed degrees
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc();
+    let coordinate = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(coordinate),
+        "S" | "W" => Some(-coordinate),
+        _ => None,
+    }
+}
+
+/// The values of one row, made up of all the sentences reported at one time
+#[derive(Default)]
+struct Epoch {
+    /// days since the first row
+    day_offset: i64,
+    time: NaiveTime,
+    date: Option<NaiveDate>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    fix: Option<&'static str>,
+    altitude_msl: Option<f64>,
+    altitude_gps: Option<f64>,
+    altitude_baro: Option<f64>,
+    ground_speed: Option<f64>,
+    track: Option<f64>,
+    heading: Option<f64>,
+    magnetic_variation: Option<f64>,
+}
+
+impl Epoch {
+    fn has_position(&self) -> bool {
+        self.latitude.is_some() && self.longitude.is_some()
+    }
+}
+
+/// A flight read from a NMEA sentence log
+pub struct NmeaLog {
+    /// One row per reported time, using the same column names as the Garmin flight data
+    pub data: DataFrame,
+    pub stats: SentenceStats,
+}
+
+impl NmeaLog {
+    /// Check if the text looks like a NMEA log, that is it holds a valid sentence of a supported type
+    pub fn is_nmea(text: &str) -> bool {
+        text.lines()
+            .filter_map(parse_sentence)
+            .any(|fields| matches!(sentence_type(fields[0]), "GGA" | "RMC" | "VTG" | "PGRMZ" | "HDT"))
+    }
+
+    pub fn from_file(path: &Path) -> PolarsResult<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> PolarsResult<Self> {
+        let mut stats = SentenceStats::default();
+        let mut epochs: Vec<Epoch> = Vec::new();
+        let mut day_offset = 0;
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = match parse_sentence(&line) {
+                Some(fields) => fields,
+                None if line.contains('$') => {
+                    stats.checksum_errors += 1;
+                    continue;
+                }
+                None => {
+                    stats.unsupported += 1;
+                    continue;
+                }
+            };
+            let field = |i: usize| fields.get(i).copied().unwrap_or("");
+
+            // sentences with a time start a new row, unless they repeat the time of the current row
+            let kind = sentence_type(fields[0]);
+            let time = match kind {
+                "GGA" | "RMC" => match parse_time(field(1)) {
+                    Some(time) => Some(time),
+                    None => {
+                        stats.unsupported += 1;
+                        continue;
+                    }
+                },
+                "VTG" | "PGRMZ" | "HDT" => None,
+                _ => {
+                    stats.unsupported += 1;
+                    continue;
+                }
+            };
+            if let Some(time) = time {
+                match epochs.last() {
+                    Some(epoch) if epoch.time == time => (),
+                    last => {
+                        // the time of day only goes backwards by a large amount when crossing midnight
+                        if matches!(last, Some(epoch) if seconds(time) + SECONDS_PER_HALF_DAY < seconds(epoch.time)) {
+                            day_offset += 1;
+                        }
+                        epochs.push(Epoch {
+                            day_offset,
+                            time,
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            // nothing can be timestamped until the first sentence with a time is seen
+            let epoch = match epochs.last_mut() {
+                Some(epoch) => epoch,
+                None => continue,
+            };
+            stats.sentences += 1;
+
+            match kind {
+                "GGA" => {
+                    let fix = match field(6) {
+                        "1" => "3D",
+                        "2" | "9" => "3DDiff",
+                        _ => "NoSoln",
+                    };
+                    epoch.fix = Some(fix);
+                    if fix != "NoSoln" {
+                        epoch.latitude = parse_coordinate(field(2), field(3));
+                        epoch.longitude = parse_coordinate(field(4), field(5));
+                        epoch.altitude_msl = parse_f64(field(9)).map(|m| m * FEET_PER_METER);
+                        epoch.altitude_gps = parse_f64(field(9))
+                            .zip(parse_f64(field(11)))
+                            .map(|(msl, separation)| (msl + separation) * FEET_PER_METER);
+                    }
+                }
+                "RMC" => {
+                    epoch.date = parse_date(field(9));
+                    if field(2) == "A" {
+                        epoch.latitude = epoch.latitude.or(parse_coordinate(field(3), field(4)));
+                        epoch.longitude = epoch.longitude.or(parse_coordinate(field(5), field(6)));
+                        epoch.ground_speed = parse_f64(field(7));
+                        epoch.track = parse_f64(field(8));
+                        epoch.magnetic_variation = parse_f64(field(10)).map(|v| match field(11) {
+                            "W" => -v,
+                            _ => v,
+                        });
+                    }
+                }
+                "VTG" => {
+                    epoch.track = epoch.track.or(parse_f64(field(1)));
+                    epoch.ground_speed = epoch.ground_speed.or(parse_f64(field(5)));
+                }
+                "PGRMZ" => {
+                    epoch.altitude_baro = parse_f64(field(1)).map(|altitude| match field(2) {
+                        "m" => altitude * FEET_PER_METER,
+                        _ => altitude,
+                    });
+                }
+                "HDT" => epoch.heading = parse_f64(field(1)),
+                _ => unreachable!(),
+            }
+        }
+
+        // every row is dated from the nearest preceding RMC date, or the first one for rows before any RMC
+        let mut base_date = epochs
+            .iter()
+            .find_map(|e| e.date.map(|date| date - TimeDelta::days(e.day_offset)))
+            .ok_or_else(|| polars_err!(ComputeError: "No RMC sentence with a date was found"))?;
+        let mut timestamps = Vec::new();
+        for epoch in &epochs {
+            if let Some(date) = epoch.date {
+                base_date = date - TimeDelta::days(epoch.day_offset);
+            }
+            let date = base_date + TimeDelta::days(epoch.day_offset);
+            timestamps.push(date.and_time(epoch.time).and_utc().timestamp_micros());
+        }
+
+        // only rows with a position make up the flight
+        let (epochs, timestamps): (Vec<Epoch>, Vec<i64>) =
+            epochs.into_iter().zip(timestamps).filter(|(e, _)| e.has_position()).unzip();
+
+        Ok(Self {
+            data: Self::build_frame(&epochs, timestamps)?,
+            stats,
+        })
+    }
+
+    fn build_frame(epochs: &[Epoch], timestamps: Vec<i64>) -> PolarsResult<DataFrame> {
+        let values = |f: fn(&Epoch) -> Option<f64>| epochs.iter().map(f).collect::<Vec<_>>();
+
+        // NMEA has no attitude, so the aircraft is shown wings level, pointing along its track. Without a barometric
+        // altitude, the GPS altitude above mean sea level stands in for it.
+        let zero = vec![0.0; epochs.len()];
+        DataFrame::new(vec![
+            Column::new("Latitude".into(), values(|e| e.latitude)),
+            Column::new("Longitude".into(), values(|e| e.longitude)),
+            Column::new("AltB".into(), values(|e| e.altitude_baro.or(e.altitude_msl))),
+            Column::new("AltMSL".into(), values(|e| e.altitude_msl)),
+            Column::new("GndSpd".into(), values(|e| e.ground_speed)),
+            Column::new("Pitch".into(), zero.clone()),
+            Column::new("Roll".into(), zero),
+            Column::new("HDG".into(), values(|e| e.heading.or(e.track))),
+            Column::new("TRK".into(), values(|e| e.track)),
+            Column::new("AltGPS".into(), values(|e| e.altitude_gps)),
+            Column::new("MagVar".into(), values(|e| e.magnetic_variation)),
+            Column::new("GPSfix".into(), epochs.iter().map(|e| e.fix).collect::<Vec<_>>()),
+            Series::new("Timestamp".into(), timestamps)
+                .cast(&DataType::Datetime(TimeUnit::Microseconds, Some("UTC".into())))?
+                .into(),
+        ])
+    }
+}
@@ -0,0 +1,372 @@
+//! Data quality of flight data: spikes, position jumps, and frozen or stuck sensors
+//!
+//! - A spike is a single row whose value departs from the rows either side of it by many times both the usual change
+//!   of the column from row to row and the change across the row, as when a probe drops out or the AHRS resets.
+//!   Steps, ramps and noise are not spikes.
+//! - A position jump is a single fix that could only be reached from the fixes before and after it at an
+//!   implausible speed.
+//! - A frozen value is a run of identical values in the air, in a column that otherwise keeps changing.
+//! - A stuck sensor is an engine or air data column holding a single value for the whole time in the air.
+//!
+//! Spikes and position jumps can be filtered out before export, replacing them with values interpolated from the
+//! rows around them.
+
+use crate::analysis::phase::PhaseClassifier;
+use crate::data::{column_f64, elapsed_seconds, timestamps};
+use crate::geo::positions;
+use crate::resample::Interpolation;
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// The thresholds used to find data quality issues
+#[derive(Debug, Clone)]
+pub struct QualityChecker {
+    /// fraction of the changes of a column from row to row that are no larger than its usual change
+    pub usual_change: f64,
+    /// How many times the usual change of a column, or the change across it, a spike departs from the rows around it
+    pub spike_factor: f64,
+    /// kt, the highest plausible speed between fixes
+    pub max_speed: f64,
+    /// rows, the shortest run of identical values that is frozen
+    pub frozen_rows: usize,
+    /// fraction of the rows in the air in which a column changes, for it to be expected to keep changing
+    pub min_change_fraction: f64,
+}
+
+impl Default for QualityChecker {
+    fn default() -> Self {
+        Self {
+            usual_change: 0.95,
+            spike_factor: 5.0,
+            max_speed: 400.0,
+            frozen_rows: 60,
+            min_change_fraction: 0.5,
+        }
+    }
+}
+
+/// The kind of a data quality issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    Spike,
+    PositionJump,
+    Frozen,
+    Stuck,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::Spike => f.write_str("spike"),
+            IssueKind::PositionJump => f.write_str("position jump"),
+            IssueKind::Frozen => f.write_str("frozen"),
+            IssueKind::Stuck => f.write_str("stuck"),
+        }
+    }
+}
+
+/// A data quality issue in a run of rows of a column
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityIssue {
+    /// The column, or "Latitude/Longitude" for a position jump
+    pub column: String,
+    pub kind: IssueKind,
+    /// The first row of the issue
+    pub start: usize,
+    /// The row after the last row of the issue
+    pub end: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// The value of the spike, or of the frozen or stuck column
+    pub value: Option<f64>,
+}
+
+/// The data quality issues of flight data, in the order they started
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityReport {
+    pub rows: usize,
+    pub issues: Vec<QualityIssue>,
+}
+
+impl QualityReport {
+    /// The number of issues of a kind
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|i| i.kind == kind).count()
+    }
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| t.format("%H:%M:%S").to_string());
+        writeln!(
+            f,
+            "{} rows: {} spikes, {} position jumps, {} frozen, {} stuck",
+            self.rows,
+            self.count(IssueKind::Spike),
+            self.count(IssueKind::PositionJump),
+            self.count(IssueKind::Frozen),
+            self.count(IssueKind::Stuck)
+        )?;
+        for issue in &self.issues {
+            let value = issue.value.map_or(String::new(), |v| format!(", {}", v));
+            match issue.kind {
+                IssueKind::Spike | IssueKind::PositionJump => writeln!(
+                    f,
+                    "  {} {} at {} (row {}){}",
+                    issue.column,
+                    issue.kind,
+                    time(issue.start_time),
+                    issue.start,
+                    value
+                )?,
+                IssueKind::Frozen | IssueKind::Stuck => writeln!(
+                    f,
+                    "  {} {} from {} to {}{}",
+                    issue.column,
+                    issue.kind,
+                    time(issue.start_time),
+                    time(issue.end_time),
+                    value
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Engine and air data columns that always vary in flight, so a single value for the whole flight is a stuck sensor
+fn varies_in_flight(column: &str) -> bool {
+    matches!(
+        column,
+        "OAT" | "IAS" | "GndSpd" | "VSpd" | "Pitch" | "Roll" | "HDG" | "TRK" | "NormAc" | "LatAc"
+    ) || column.starts_with("E1 CHT")
+        || column.starts_with("E1 EGT")
+        || column.starts_with("E1 TIT")
+        || matches!(column, "E1 OilT" | "E1 OilP" | "E1 MAP" | "E1 RPM" | "E1 FFlow")
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    Some(match values.len() % 2 {
+        0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    })
+}
+
+/// Replace the values of the rows marked bad, interpolating in time between the nearest good rows around them
+fn fill_between(values: &mut [Option<f64>], bad: &[bool], elapsed: &[Option<f64>], interpolation: Interpolation) {
+    let original = values.to_vec();
+    let good = |row: usize| !bad[row] && original[row].is_some();
+    for row in (0..values.len()).filter(|&r| bad[r]) {
+        let before = (0..row).rev().find(|&r| good(r));
+        let after = (row + 1..values.len()).find(|&r| good(r));
+        values[row] = match (before, after) {
+            (Some(before), Some(after)) => (|| {
+                let fraction = (elapsed[row]? - elapsed[before]?) / (elapsed[after]? - elapsed[before]?);
+                Some(interpolation.interpolate(original[before]?, original[after]?, fraction))
+            })(),
+            _ => None,
+        };
+    }
+}
+
+impl QualityChecker {
+    /// The rows of a column that depart from the values around them, as a spike
+    fn spikes(&self, values: &[Option<f64>], interpolation: Interpolation) -> Vec<bool> {
+        let n = values.len();
+        let mut spikes = vec![false; n];
+
+        // the usual change from row to row, the largest but for the few largest changes
+        let mut changes = values
+            .windows(2)
+            .filter_map(|v| Some(interpolation.difference(v[1]?, v[0]?).abs()))
+            .collect::<Vec<_>>();
+        changes.sort_by(f64::total_cmp);
+        let usual = changes[..(changes.len() as f64 * self.usual_change).ceil() as usize].last();
+        let Some(&usual) = usual.filter(|&&c| c > 0.0) else {
+            return spikes;
+        };
+
+        // a spike departs from the rows either side of it by far more than they differ from each other
+        for row in 1..n.saturating_sub(1) {
+            let (Some(before), Some(value), Some(after)) = (values[row - 1], values[row], values[row + 1]) else {
+                continue;
+            };
+            let departure = interpolation.difference(value, interpolation.interpolate(before, after, 0.5));
+            let spread = interpolation.difference(after, before).abs().max(usual);
+            spikes[row] = departure.abs() > self.spike_factor * spread;
+        }
+        spikes
+    }
+
+    /// The rows whose fix is too far from the fixes before and after it to have been flown to
+    fn position_jumps(&self, df: &DataFrame) -> Vec<bool> {
+        let positions = positions(df);
+        let elapsed = elapsed_seconds(df);
+        let known = (0..df.height())
+            .filter(|&r| positions[r].is_some() && elapsed[r].is_some())
+            .collect::<Vec<_>>();
+        let speed = |a: usize, b: usize| {
+            let hours = (elapsed[b].unwrap() - elapsed[a].unwrap()) / 3600.0;
+            let distance = positions[a].unwrap().distance_nm(&positions[b].unwrap());
+            if hours > 0.0 {
+                distance / hours
+            } else if distance > 0.0 {
+                f64::INFINITY
+            } else {
+                0.0
+            }
+        };
+        let mut jumps = vec![false; df.height()];
+        for fixes in known.windows(3) {
+            let (before, row, after) = (fixes[0], fixes[1], fixes[2]);
+            jumps[row] = speed(before, row) > self.max_speed && speed(row, after) > self.max_speed;
+        }
+        jumps
+    }
+
+    /// The columns that are interpolated, with how
+    fn numeric_columns(df: &DataFrame) -> Vec<(String, Interpolation)> {
+        df.get_columns()
+            .iter()
+            .filter(|c| !matches!(c.name().as_str(), "Timestamp" | "Latitude" | "Longitude"))
+            .map(|c| (c.name().to_string(), Interpolation::of(c.name(), c.dtype())))
+            .filter(|(_, interpolation)| *interpolation != Interpolation::Step)
+            .collect()
+    }
+
+    /// Find the data quality issues of flight data
+    pub fn check(&self, df: &DataFrame) -> QualityReport {
+        let n = df.height();
+        let timestamps = timestamps(df);
+        let phases = PhaseClassifier::default().classify(df);
+        let airborne = phases.phases.iter().map(|p| p.is_airborne()).collect::<Vec<_>>();
+        let airborne_rows = airborne.iter().filter(|&&a| a).count();
+        let mut issues = Vec::new();
+        let mut issue = |column: &str, kind: IssueKind, start: usize, end: usize, value: Option<f64>| {
+            issues.push(QualityIssue {
+                column: column.to_string(),
+                kind,
+                start,
+                end,
+                start_time: timestamps[start],
+                end_time: timestamps[end - 1],
+                value,
+            })
+        };
+
+        for (row, _) in self.position_jumps(df).into_iter().enumerate().filter(|(_, j)| *j) {
+            issue("Latitude/Longitude", IssueKind::PositionJump, row, row + 1, None);
+        }
+
+        for (column, interpolation) in Self::numeric_columns(df) {
+            let values = column_f64(df, &column);
+            for (row, _) in self
+                .spikes(&values, interpolation)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, s)| *s)
+            {
+                issue(&column, IssueKind::Spike, row, row + 1, values[row]);
+            }
+
+            // runs of identical values in the air
+            let in_air = (0..n)
+                .filter(|&r| airborne[r] && values[r].is_some())
+                .collect::<Vec<_>>();
+            if in_air.len() < self.frozen_rows {
+                continue;
+            }
+            let changes = in_air.windows(2).filter(|r| values[r[0]] != values[r[1]]).count();
+            if changes == 0 && in_air.len() == airborne_rows && varies_in_flight(&column) {
+                issue(
+                    &column,
+                    IssueKind::Stuck,
+                    in_air[0],
+                    in_air[in_air.len() - 1] + 1,
+                    values[in_air[0]],
+                );
+                continue;
+            }
+            if (changes as f64) < self.min_change_fraction * (in_air.len() - 1) as f64 {
+                continue;
+            }
+            let mut start = 0;
+            for i in 1..=in_air.len() {
+                let continues =
+                    i < in_air.len() && in_air[i] == in_air[i - 1] + 1 && values[in_air[i]] == values[in_air[start]];
+                if !continues {
+                    if i - start >= self.frozen_rows {
+                        issue(
+                            &column,
+                            IssueKind::Frozen,
+                            in_air[start],
+                            in_air[i - 1] + 1,
+                            values[in_air[start]],
+                        );
+                    }
+                    start = i;
+                }
+            }
+        }
+
+        issues.sort_by_key(|i| i.start);
+        QualityReport { rows: n, issues }
+    }
+
+    /// Replace the spikes and position jumps with values interpolated from the rows around them
+    pub fn despike(&self, df: &DataFrame) -> PolarsResult<DataFrame> {
+        let elapsed = elapsed_seconds(df);
+        let mut df = df.clone();
+        let jumps = self.position_jumps(&df);
+        let replace = |df: &mut DataFrame, column: &str, bad: &[bool], interpolation| -> PolarsResult<()> {
+            if !bad.contains(&true) {
+                return Ok(());
+            }
+            let mut values = column_f64(df, column);
+            fill_between(&mut values, bad, &elapsed, interpolation);
+            df.with_column(Column::new(column.into(), values))?;
+            Ok(())
+        };
+        for column in ["Latitude", "Longitude"] {
+            replace(&mut df, column, &jumps, Interpolation::Linear)?;
+        }
+        for (column, interpolation) in Self::numeric_columns(&df) {
+            let spikes = self.spikes(&column_f64(&df, &column), interpolation);
+            replace(&mut df, &column, &spikes, interpolation)?;
+        }
+        Ok(df)
+    }
+}
+
+/// Smooth the continuous columns with a centered moving median over a window of rows, ignoring missing values
+pub fn median_filter(df: &DataFrame, window_rows: usize) -> PolarsResult<DataFrame> {
+    let half = window_rows / 2;
+    let mut filtered = df.clone();
+    for (column, interpolation) in QualityChecker::numeric_columns(df) {
+        if interpolation != Interpolation::Linear {
+            continue;
+        }
+        let values = column_f64(df, &column);
+        let smoothed = (0..values.len())
+            .map(|row| {
+                values[row]?;
+                let mut window = values[row.saturating_sub(half)..(row + half + 1).min(values.len())]
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+                median(&mut window)
+            })
+            .collect::<Vec<_>>();
+        filtered.with_column(Column::new(column.into(), smoothed))?;
+    }
+    Ok(filtered)
+}
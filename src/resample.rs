@@ -0,0 +1,185 @@
+//! Resampling of flight data to a fixed rate
+//!
+//! Logs are recorded at a nominal rate, but have gaps, duplicate timestamps and jitter. Resampling produces rows at
+//! exactly the given rate, from the first timestamp to the last. Each column is interpolated by the kind of value it
+//! holds:
+//!
+//! - continuous values are interpolated linearly between the rows around each time
+//! - angles (headings, tracks and roll) are interpolated along the shortest arc, so 359 and 1 give 0 rather than 180
+//! - enumerations, booleans and text hold the value of the last row at or before each time
+//!
+//! Gaps longer than the `max_gap` are not filled in, leaving the values of the rows in them missing. Rows without
+//! a timestamp are dropped, and of the rows sharing a timestamp only the last is kept, as when the time goes
+//! backwards the later rows follow a correction of the clock.
+
+use crate::data::timestamps;
+use crate::units::canonical_unit;
+use polars::prelude::*;
+
+/// Hz, the highest rate data is resampled to, above which the rows would only repeat the interpolation
+pub const MAX_RATE: f64 = 100.0;
+
+/// How the values of a column are interpolated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Linear between the rows around each time
+    Linear,
+    /// Along the shortest arc, wrapped to 0..360 degrees
+    Heading,
+    /// Along the shortest arc, wrapped to -180..180 degrees
+    Bank,
+    /// The value of the last row at or before each time
+    Step,
+}
+
+impl Interpolation {
+    /// The interpolation of a column, from its name and type
+    pub fn of(column: &str, dtype: &DataType) -> Self {
+        match column {
+            "HDG" | "TRK" | "CRS" | "WndDr" | "WptBrg" => Interpolation::Heading,
+            "Roll" | "RollC" => Interpolation::Bank,
+            _ if !dtype.is_numeric() => Interpolation::Step,
+            _ if matches!(canonical_unit(column), Some("enum") | Some("bool")) => Interpolation::Step,
+            _ => Interpolation::Linear,
+        }
+    }
+
+    /// The value a fraction of the way from one value to the next
+    pub fn interpolate(&self, from: f64, to: f64, fraction: f64) -> f64 {
+        let arc = || self.difference(to, from);
+        match self {
+            Interpolation::Linear => from + (to - from) * fraction,
+            Interpolation::Heading => (from + arc() * fraction).rem_euclid(360.0),
+            Interpolation::Bank => (from + arc() * fraction + 180.0).rem_euclid(360.0) - 180.0,
+            Interpolation::Step => from,
+        }
+    }
+
+    /// The difference of two values, along the shortest arc for angles
+    pub fn difference(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Interpolation::Heading | Interpolation::Bank => (a - b + 180.0).rem_euclid(360.0) - 180.0,
+            _ => a - b,
+        }
+    }
+}
+
+/// Resamples flight data to a fixed rate
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// Hz, the rows per second of the resampled data
+    pub rate: f64,
+    /// s, the longest gap between rows that is interpolated across
+    pub max_gap: f64,
+}
+
+impl Default for Resampler {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            max_gap: 5.0,
+        }
+    }
+}
+
+impl Resampler {
+    /// A resampler to the rate, which must be positive and at most the `MAX_RATE`
+    pub fn new(rate: f64) -> Result<Self, String> {
+        if !(rate > 0.0 && rate <= MAX_RATE) {
+            return Err(format!(
+                "Resampling rate must be above 0 and at most {} Hz, not {}",
+                MAX_RATE, rate
+            ));
+        }
+        Ok(Self {
+            rate,
+            ..Default::default()
+        })
+    }
+
+    /// Resample flight data with a "Timestamp" column to the rate
+    pub fn resample(&self, df: &DataFrame) -> PolarsResult<DataFrame> {
+        if !(self.rate > 0.0 && self.rate <= MAX_RATE) {
+            polars_bail!(
+                InvalidOperation: "resampling rate must be above 0 and at most {} Hz, not {}", MAX_RATE, self.rate
+            );
+        }
+        df.column("Timestamp")?;
+        let step = (1e6 / self.rate).round().max(1.0) as i64;
+        let max_gap = (self.max_gap * 1e6).round() as i64;
+
+        // the rows with a timestamp, in time order, without duplicate timestamps
+        let mut rows = timestamps(df)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, t)| Some((t?.timestamp_micros(), row)))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|&(time, row)| (time, std::cmp::Reverse(row)));
+        rows.dedup_by_key(|&mut (time, _)| time);
+        let (Some(&(first, _)), Some(&(last, _))) = (rows.first(), rows.last()) else {
+            return Ok(df.clear());
+        };
+        let times = (0..=(last - first) / step)
+            .map(|i| first + i * step)
+            .collect::<Vec<_>>();
+
+        // the last row at or before each time
+        let mut previous = Vec::with_capacity(times.len());
+        let mut next = 0;
+        for &time in &times {
+            while next < rows.len() && rows[next].0 <= time {
+                next += 1;
+            }
+            previous.push(rows[next - 1]);
+        }
+
+        let mut columns = Vec::with_capacity(df.width());
+        for column in df.get_columns() {
+            let name = column.name().clone();
+            if name.as_str() == "Timestamp" {
+                let timestamps = Int64Chunked::from_vec(name, times.clone())
+                    .into_datetime(TimeUnit::Microseconds, Some("UTC".into()));
+                columns.push(timestamps.into_column());
+                continue;
+            }
+            let interpolation = Interpolation::of(&name, column.dtype());
+            if interpolation == Interpolation::Step {
+                let indices = times
+                    .iter()
+                    .zip(&previous)
+                    .map(|(&time, &(before, row))| (time - before <= max_gap).then_some(row as IdxSize))
+                    .collect::<IdxCa>();
+                columns.push(column.take(&indices)?);
+                continue;
+            }
+
+            // interpolate between the nearest rows with a value around each time
+            let values = column.cast(&DataType::Float64)?;
+            let values = values.f64()?;
+            let known = rows
+                .iter()
+                .filter_map(|&(time, row)| Some((time, values.get(row)?)))
+                .collect::<Vec<_>>();
+            let mut next = 0;
+            let resampled = times
+                .iter()
+                .map(|&time| {
+                    while next < known.len() && known[next].0 <= time {
+                        next += 1;
+                    }
+                    let (before, value) = known[..next].last().copied()?;
+                    if before == time {
+                        return Some(value);
+                    }
+                    let (after, to) = known.get(next).copied()?;
+                    (after - before <= max_gap).then(|| {
+                        let fraction = (time - before) as f64 / (after - before) as f64;
+                        interpolation.interpolate(value, to, fraction)
+                    })
+                })
+                .collect::<Float64Chunked>();
+            columns.push(resampled.with_name(name).into_column());
+        }
+        DataFrame::new(columns)
+    }
+}
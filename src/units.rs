@@ -0,0 +1,143 @@
+//! Units of measure of the flight data, and conversions between them
+//!
+//! Every reader produces columns named and scaled like those of the Garmin EIS log, so that exports and analyses
+//! work regardless of where the data came from. Data in other units is converted into the canonical unit of its
+//! column when it is read.
+
+/// The canonical unit of a flight data column, following the units of the Garmin EIS log. Returns None for columns
+/// that are not known.
+pub fn canonical_unit(column: &str) -> Option<&'static str> {
+    let unit = match column {
+        "Latitude" | "Longitude" => "deg",
+        "AltB" | "AltMSL" | "AltGPS" | "AltPress" => "ft",
+        "BaroA" | "E1 MAP" => "inHg",
+        "OAT" => "deg C",
+        "IAS" | "GndSpd" | "TAS" | "WndSpd" => "kt",
+        "VSpd" | "VSpdG" => "fpm",
+        "Pitch" | "Roll" | "HDG" | "TRK" | "CRS" | "WndDr" | "WptBrg" | "MagVar" | "RollC" | "PitchC" => "deg",
+        "LatAc" | "NormAc" | "LonAc" => "G",
+        "bus1volts" => "volts",
+        "alt1amps" => "amps",
+        "FQtyL" | "FQtyR" => "gals",
+        "FQtyLlbs" | "FQtyRlbs" => "lbs",
+        "E1 FFlow" => "gph",
+        "E1 FPres" | "E1 OilP" => "psi",
+        "E1 OilT" => "deg F",
+        "E1 RPM" => "rpm",
+        "E1 %Pwr" => "%",
+        "E1 CHT CLD" => "deg F/min",
+        "HCDI" | "VCDI" => "fsd",
+        "WptDst" => "nm",
+        "NAV1" | "NAV2" => "MHz",
+        "HAL" | "VAL" | "HPLwas" | "HPLfd" | "VPLwas" => "m",
+        "AtvWpt" | "HSIS" | "RollM" | "PitchM" | "GPSfix" => "enum",
+        "AfcsOn" | "OnGrnd" => "bool",
+        "LogIdx" => "#",
+        "SysTime" => "s",
+        "Hobbs" | "FltTime" => "h",
+        c if c.starts_with("E1 CHT") || c.starts_with("E1 EGT") || c.starts_with("E1 TIT") => "deg F",
+        _ => return None,
+    };
+    Some(unit)
+}
+
+/// The quantity a unit measures, with the scale and offset to convert a value into the base unit of that quantity
+struct UnitDefinition {
+    quantity: &'static str,
+    scale: f64,
+    offset: f64,
+}
+
+fn definition(unit: &str) -> Option<UnitDefinition> {
+    let (quantity, scale, offset) = match unit.trim() {
+        "m" | "mt" | "meters" => ("length", 1.0, 0.0),
+        "ft" | "feet" | "ft Baro" | "ft msl" | "ft wgs" => ("length", 0.3048, 0.0),
+        "nm" => ("length", 1852.0, 0.0),
+        "km" => ("length", 1000.0, 0.0),
+        "sm" | "mi" => ("length", 1609.344, 0.0),
+        "m/s" => ("speed", 1.0, 0.0),
+        "kt" | "kts" | "knots" => ("speed", 1852.0 / 3600.0, 0.0),
+        "mph" => ("speed", 0.44704, 0.0),
+        "km/h" | "kph" => ("speed", 1.0 / 3.6, 0.0),
+        "fpm" | "ft/min" => ("speed", 0.00508, 0.0),
+        "m/min" => ("speed", 1.0 / 60.0, 0.0),
+        "deg" | "degrees" => ("angle", 1.0, 0.0),
+        "rad" => ("angle", 180.0 / std::f64::consts::PI, 0.0),
+        "K" => ("temperature", 1.0, 0.0),
+        "deg C" | "degC" | "C" => ("temperature", 1.0, 273.15),
+        "deg F" | "degF" | "F" => ("temperature", 5.0 / 9.0, 459.67 * 5.0 / 9.0),
+        "deg C/min" => ("temperature rate", 1.0, 0.0),
+        "deg F/min" => ("temperature rate", 5.0 / 9.0, 0.0),
+        "Pa" => ("pressure", 1.0, 0.0),
+        "hPa" | "mbar" => ("pressure", 100.0, 0.0),
+        "kPa" => ("pressure", 1000.0, 0.0),
+        "bar" => ("pressure", 100_000.0, 0.0),
+        "psi" => ("pressure", 6894.757, 0.0),
+        "inHg" | "Hg" | "inch" => ("pressure", 3386.389, 0.0),
+        "l" | "L" | "liters" => ("volume", 1.0, 0.0),
+        "gals" | "gal" => ("volume", 3.785411784, 0.0),
+        "l/h" | "lph" => ("flow", 1.0, 0.0),
+        "gph" => ("flow", 3.785411784, 0.0),
+        "kg" => ("mass", 1.0, 0.0),
+        "lbs" | "lb" => ("mass", 0.45359237, 0.0),
+        "G" | "g" => ("acceleration", 1.0, 0.0),
+        "m/s2" | "m/s^2" => ("acceleration", 1.0 / 9.80665, 0.0),
+        "s" => ("time", 1.0, 0.0),
+        "min" => ("time", 60.0, 0.0),
+        "h" | "hrs" => ("time", 3600.0, 0.0),
+        _ => return None,
+    };
+    Some(UnitDefinition {
+        quantity,
+        scale,
+        offset,
+    })
+}
+
+/// The name understood by [`conversion`] of a unit as written in a log, where it is spelled differently, such as `°F`,
+/// `DEG F` or `IN.HG`. Other units are returned as written, so that a unit that is not known is never taken for the
+/// canonical unit of a column.
+pub fn normalize_unit(unit: &str) -> String {
+    let unit = unit.trim();
+    let name = match unit.to_uppercase().replace(['.', ' ', '°'], "").as_str() {
+        "F" | "DEGF" => "deg F",
+        "C" | "DEGC" => "deg C",
+        "PSI" => "psi",
+        "KPA" => "kPa",
+        "HPA" | "MBAR" => "hPa",
+        "BAR" => "bar",
+        "INHG" => "inHg",
+        "GPH" => "gph",
+        "LPH" | "L/H" => "l/h",
+        "GAL" | "GALS" => "gals",
+        "L" | "LTR" => "l",
+        "LB" | "LBS" => "lbs",
+        "KG" => "kg",
+        "HRS" | "HR" => "h",
+        "RPM" => "rpm",
+        "V" | "VOLTS" => "volts",
+        "A" | "AMPS" => "amps",
+        "FT" | "FEET" => "ft",
+        "KT" | "KTS" | "KNOTS" => "kt",
+        "FPM" | "FT/MIN" => "fpm",
+        "DEG" | "DEGREES" => "deg",
+        _ => return unit.to_string(),
+    };
+    name.to_string()
+}
+
+/// The scale and offset that convert a value from one unit to another, as `to = from * scale + offset`.
+///
+/// Returns None if either unit is unknown or the units do not measure the same quantity. Identical units always
+/// convert, even when unknown.
+pub fn conversion(from: &str, to: &str) -> Option<(f64, f64)> {
+    if from.trim() == to.trim() {
+        return Some((1.0, 0.0));
+    }
+    let from = definition(from)?;
+    let to = definition(to)?;
+    if from.quantity != to.quantity {
+        return None;
+    }
+    Some((from.scale / to.scale, (from.offset - to.offset) / to.scale))
+}
@@ -0,0 +1,60 @@
+use hangar::airport::{AirportDatabase, AirportEvent};
+use hangar::analysis::summary::FlightSummary;
+use hangar::avionics::AvionicsLogSource;
+use hangar::geo::Position;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J, and the airports and runways around its flight, from OurAirports
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+const AIRPORTS_CSV: &str = "airports_231104.csv";
+const RUNWAYS_CSV: &str = "runways_231104.csv";
+
+fn sample_airports() -> Result<AirportDatabase, String> {
+    AirportDatabase::from_csv(&resource_path(AIRPORTS_CSV), Some(&resource_path(RUNWAYS_CSV)))
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn read_airports() -> Result<(), String> {
+    let airports = sample_airports()?;
+
+    // the heliport is skipped
+    assert_eq!(airports.airports.len(), 7);
+    let (airport, distance) = airports.nearest(&Position::new(41.63, -73.88), 3.0).unwrap();
+    assert_eq!(airport.ident, "KPOU");
+    assert!(distance < 0.5);
+    let runways = airport.runways.iter().map(|r| r.ident.as_str()).collect::<Vec<_>>();
+    assert_eq!(runways, ["06", "24", "15", "33"]);
+    assert!(airports.nearest(&Position::new(42.5, -73.0), 3.0).is_none());
+    Ok(())
+}
+
+#[test]
+fn locate_takeoff_and_landing() -> Result<(), String> {
+    let airports = sample_airports()?;
+    let mut log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let visits = log.identify_airports(&airports);
+
+    let found = visits
+        .iter()
+        .map(|v| (v.event, v.airport.as_str(), v.runway.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        [
+            (AirportEvent::Takeoff, "KPOU", Some("24")),
+            (AirportEvent::Landing, "N40", Some("25"))
+        ]
+    );
+    assert_eq!(
+        visits[0].to_string(),
+        "Takeoff from KPOU Hudson Valley Regional Airport, runway 24 at 13:02:28"
+    );
+    assert_eq!(log.arrival_airport.as_deref(), Some("N40"));
+
+    let summary = FlightSummary::from_log(&log).with_airports(&visits);
+    assert_eq!(summary.departure_runway.as_deref(), Some("24"));
+    assert_eq!(summary.arrival_airport.as_deref(), Some("N40"));
+    assert_eq!(summary.arrival_runway.as_deref(), Some("25"));
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use hangar::analysis::approach::StabilizedApproachCriteria;
+use hangar::analysis::phase::PhaseClassifier;
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn evaluate_sample_approach() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    // a visual approach, flown with a GPS course to a waypoint off to the side
+    let evaluations = StabilizedApproachCriteria::default().evaluate(&log.data, &phases);
+    assert_eq!(evaluations.len(), 1);
+    let evaluation = &evaluations[0];
+    assert_eq!(evaluation.touchdown, 3457);
+    assert!((evaluation.field_elevation - 543.4).abs() < 0.1);
+    assert_eq!(evaluation.stabilized, Some(false));
+    assert_eq!(evaluation.reasons.len(), 1);
+    assert!(evaluation.reasons[0].starts_with("Lateral deviation (HCDI)"));
+    assert!(evaluation.notes.iter().any(|n| n.starts_with("Configuration")));
+    assert!(evaluation
+        .notes
+        .iter()
+        .any(|n| n.starts_with("Vertical deviation (VCDI)")));
+
+    let visual = StabilizedApproachCriteria {
+        max_hcdi: None,
+        max_vcdi: None,
+        ..Default::default()
+    };
+    let evaluation = &visual.evaluate(&log.data, &phases)[0];
+    assert_eq!(evaluation.stabilized, Some(true));
+    assert!(evaluation.max_sink_rate.unwrap() < 1000.0);
+    assert!(evaluation.max_bank.unwrap() < 15.0);
+    Ok(())
+}
+
+#[test]
+fn approach_speed_and_gates() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    let slow = StabilizedApproachCriteria {
+        approach_speed: Some(70.0),
+        max_hcdi: None,
+        ..Default::default()
+    };
+    let evaluation = &slow.evaluate(&log.data, &phases)[0];
+    assert_eq!(evaluation.stabilized, Some(false));
+    assert!(evaluation.reasons[0].starts_with("Airspeed above approach speed"));
+
+    // the flight never climbed 5000 ft above the landing field
+    let high = StabilizedApproachCriteria {
+        upper_gate: 5000.0,
+        ..Default::default()
+    };
+    let evaluation = &high.evaluate(&log.data, &phases)[0];
+    assert_eq!(evaluation.stabilized, None);
+    assert!(evaluation.upper_gate_time.is_none());
+    Ok(())
+}
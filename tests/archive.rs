@@ -0,0 +1,81 @@
+use hangar::archive::{find_logs, is_collection, is_zip};
+use hangar::avionics::AvionicsLogFormat;
+use hangar::resource_path;
+use std::io::Write;
+use std::path::PathBuf;
+use zip::write::SimpleFileOptions;
+
+const GARMIN_LOG: &str = "log_231104_084813_KPOU.csv";
+const NMEA_LOG: &str = "gps_231104.nmea";
+
+#[test]
+fn find_logs_in_zip() -> Result<(), String> {
+    let path = std::env::temp_dir().join("hangar_test_logs.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).map_err(|e| e.to_string())?);
+    let options = SimpleFileOptions::default();
+    for (name, contents) in [
+        (
+            format!("data_log/{}", GARMIN_LOG),
+            std::fs::read(resource_path(GARMIN_LOG)),
+        ),
+        (format!("data_log/{}", NMEA_LOG), std::fs::read(resource_path(NMEA_LOG))),
+        ("data_log/readme.txt".to_string(), Ok(b"not a log".to_vec())),
+        (
+            format!("__MACOSX/data_log/._{}", GARMIN_LOG),
+            std::fs::read(resource_path(GARMIN_LOG)),
+        ),
+    ] {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(&contents.map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    assert!(is_zip(&path).map_err(|e| e.to_string())?);
+    let (logs, errors) = find_logs(&path).map_err(|e| e.to_string())?;
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    assert!(errors.is_empty());
+
+    // the logs are found by their content, in the order of their names, skipping other files
+    let names = logs.iter().map(|log| log.name.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            PathBuf::from("data_log").join(NMEA_LOG),
+            PathBuf::from("data_log").join(GARMIN_LOG)
+        ]
+    );
+    assert_eq!(logs[0].format, AvionicsLogFormat::Nmea);
+    assert_eq!(logs[1].format, AvionicsLogFormat::Garmin);
+    assert_eq!(
+        logs[1].output_name("fdr"),
+        PathBuf::from("data_log/log_231104_084813_KPOU.fdr")
+    );
+    Ok(())
+}
+
+#[test]
+fn read_logs_in_directory() -> Result<(), String> {
+    let dir = std::env::temp_dir().join("hangar_test_sd_card");
+    let data_log = dir.join("data_log");
+    std::fs::create_dir_all(&data_log).map_err(|e| e.to_string())?;
+    std::fs::copy(resource_path(GARMIN_LOG), data_log.join(GARMIN_LOG)).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("notes.txt"), "not a log").map_err(|e| e.to_string())?;
+    // a file that cannot be opened is reported and skipped
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("missing.csv"), data_log.join("broken.csv")).map_err(|e| e.to_string())?;
+
+    assert!(is_collection(&dir).map_err(|e| e.to_string())?);
+    let (logs, errors) = find_logs(&dir).map_err(|e| e.to_string())?;
+    let log = logs.first().map(|log| log.read());
+    std::fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    assert_eq!(logs.len(), 1);
+    assert_eq!(errors.len(), usize::from(cfg!(unix)));
+    assert!(errors.iter().all(|e| e.contains("broken.csv")));
+    assert_eq!(logs[0].name, PathBuf::from("data_log").join(GARMIN_LOG));
+    let log = log.unwrap()?;
+    assert_eq!(log.data.height(), 3676);
+    assert!(log.tail_number.is_some());
+    Ok(())
+}
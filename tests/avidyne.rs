@@ -0,0 +1,57 @@
+use hangar::avidyne::{AvidyneLog, AvidyneLogHeader};
+use hangar::avionics::{detect_source, AvionicsLogSource};
+use hangar::resource_path;
+
+// An Avidyne Entegra log from a Cirrus SR22
+const SAMPLE_CSV: &str = "avidyne_231104.csv";
+
+#[test]
+fn read_header() -> Result<(), String> {
+    let header = AvidyneLogHeader::from_csv(&resource_path(SAMPLE_CSV)).map_err(|e| e.to_string())?;
+    assert_eq!(header.tail_number(), Some("N822SR"));
+    assert_eq!(header.metadata["Airframe"], "Cirrus SR22");
+    assert_eq!(header.table_offset, 4);
+    assert_eq!(header.columns[16].name, "Fuel Flow");
+    assert_eq!(header.columns[16].unit.as_deref(), Some("gph"));
+    assert_eq!(header.columns[14].unit, None);
+    Ok(())
+}
+
+#[test]
+fn read_data() -> Result<(), String> {
+    let log = AvidyneLog::from_csv(&resource_path(SAMPLE_CSV)).map_err(|e| e.to_string())?;
+    assert_eq!(log.data.height(), 12);
+
+    // unknown columns are left out, cylinders are numbered like the Garmin engine columns
+    let columns = log.data.get_column_names().iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert!(columns.contains(&"E1 CHT6".to_string()));
+    assert!(columns.contains(&"E1 EGT1".to_string()));
+    assert!(!columns.contains(&"Marker".to_string()));
+
+    // oil temperature is converted from Celsius to the Garmin unit of Fahrenheit
+    let oil = log.data.column("E1 OilT").unwrap().f64().unwrap().clone();
+    assert!((oil.get(0).unwrap() - 194.0).abs() < 1e-9);
+
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let first = timestamps.as_datetime_iter().next().flatten().unwrap();
+    assert_eq!(first.to_string(), "2023-11-04 14:10:00");
+    assert!(log.warnings.is_empty());
+
+    // units are read in any case, and a column in a unit that cannot be converted is skipped
+    let text = std::fs::read_to_string(resource_path(SAMPLE_CSV)).map_err(|e| e.to_string())?;
+    let text = text
+        .replacen("Fuel Flow (gph)", "Fuel Flow (GPH)", 1)
+        .replacen("Oil Press (psi)", "Oil Press (furlongs)", 1);
+    let respelled = AvidyneLog::from_bytes(text.into_bytes()).map_err(|e| e.to_string())?;
+    let fuel_flow = |log: &AvidyneLog| log.data.column("E1 FFlow").unwrap().clone();
+    assert!(fuel_flow(&respelled).equals(&fuel_flow(&log)));
+    assert!(respelled.data.column("E1 OilP").is_err());
+    assert_eq!(respelled.warnings.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn detect_log() {
+    let source = detect_source(&resource_path(SAMPLE_CSV)).unwrap();
+    assert!(matches!(source, Some(AvionicsLogSource::Avidyne(_))));
+}
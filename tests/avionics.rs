@@ -0,0 +1,44 @@
+use hangar::avionics::{detect_source, AvionicsLog, AvionicsLogFormat};
+use hangar::resource_path;
+
+// A sample log of each of the detected formats, with its format
+const SAMPLES: [(&str, AvionicsLogFormat); 5] = [
+    ("log_231104_084813_KPOU.csv", AvionicsLogFormat::Garmin),
+    ("avidyne_231104.csv", AvionicsLogFormat::Avidyne),
+    ("mvp50_231104.csv", AvionicsLogFormat::ElectronicsInternational),
+    ("stratux_231104.gdl90", AvionicsLogFormat::Gdl90),
+    ("gps_231104.nmea", AvionicsLogFormat::Nmea),
+];
+
+#[test]
+fn read_logs_from_memory() -> Result<(), String> {
+    for (sample, format) in SAMPLES {
+        let path = resource_path(sample);
+        let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+        assert_eq!(AvionicsLogFormat::detect(&bytes), Some(format), "{}", sample);
+
+        // the same data as reading the file, with a GDL90 capture dated by the time the file was modified
+        let source = detect_source(&path).map_err(|e| e.to_string())?.unwrap();
+        assert_eq!(source.format(), Some(format));
+        let from_file = source.read()?;
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|e| e.to_string())?;
+        let from_memory = AvionicsLog::from_reader(bytes.as_slice(), Some(modified.into()))?;
+        assert_eq!(from_memory.tail_number, from_file.tail_number, "{}", sample);
+        assert!(from_memory.data.equals_missing(&from_file.data), "{}", sample);
+    }
+    let capture = std::fs::read(resource_path("stratux_231104.gdl90")).map_err(|e| e.to_string())?;
+    assert!(AvionicsLog::from_bytes(capture, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn reject_unrecognized_bytes() {
+    assert_eq!(AvionicsLogFormat::detect(b"not,a,flight\n1,2,3\n"), None);
+    assert!(AvionicsLog::from_bytes(b"not,a,flight\n1,2,3\n".to_vec(), None).is_err());
+
+    // a file starting with a comment line is not a Garmin log
+    let limits = std::fs::read(resource_path("m20j_limits.toml")).unwrap();
+    assert_eq!(AvionicsLogFormat::detect(&limits), None);
+}
@@ -0,0 +1,53 @@
+use hangar::batch::{find_inputs, NameTemplate};
+use hangar::resource_path;
+use std::path::PathBuf;
+
+#[test]
+fn find_logs_of_inputs() {
+    // the pattern matches the CSV logs of the day, and a missing file is an error of its own
+    let pattern = resource_path("*_231104*.csv");
+    let missing = resource_path("missing.csv");
+    let (logs, errors) = find_inputs(&[pattern, resource_path("gps_231104.nmea"), missing.clone()]);
+
+    let names = logs.iter().map(|log| log.name.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "avidyne_231104.csv",
+            "cgr30_231104.csv",
+            "log_231104_084813_KPOU.csv",
+            "mvp50_231104.csv",
+            "gps_231104.nmea"
+        ]
+        .map(PathBuf::from)
+    );
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with(&missing.display().to_string()));
+}
+
+#[test]
+fn name_outputs_by_template() -> Result<(), String> {
+    assert!(NameTemplate::new("{tail}/{runway}").is_err());
+    assert!(NameTemplate::new("{tail").is_err());
+
+    let (logs, _) = find_inputs(&[resource_path("log_231104_084813_KPOU.csv")]);
+    let log = logs[0].read()?;
+    let template = NameTemplate::new("{tail}/{date}_{time}_{airport}")?;
+    assert!(template.uses_log());
+    assert_eq!(
+        template.render(&logs[0], Some(&log), "fdr"),
+        PathBuf::from("N12345/2023-11-04_124813_KPOU.fdr")
+    );
+    assert_eq!(
+        template.render(&logs[0], None, "fdr"),
+        PathBuf::from("unknown/unknown_unknown_unknown.fdr")
+    );
+
+    let template = NameTemplate::default();
+    assert!(!template.uses_log());
+    assert_eq!(
+        template.render(&logs[0], None, "fdr"),
+        PathBuf::from("log_231104_084813_KPOU.fdr")
+    );
+    Ok(())
+}
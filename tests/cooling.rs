@@ -0,0 +1,47 @@
+use hangar::analysis::cooling::{with_cht_rates, ShockCoolingAnalyzer};
+use hangar::analysis::phase::PhaseClassifier;
+use hangar::avionics::AvionicsLogSource;
+use hangar::data::column_f64;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn cht_rate_columns() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let data = with_cht_rates(log.data, 30).map_err(|e| e.to_string())?;
+
+    // cylinder 3 cools fastest on short final, as power comes off
+    let rates = column_f64(&data, "E1 CHT3 Rate");
+    assert!((rates[3440].unwrap() + 47.4).abs() < 0.1);
+    assert!(data.column("E1 CHT4 Rate").is_ok());
+    assert!(data.column("E1 CHT CLD Rate").is_err());
+    Ok(())
+}
+
+#[test]
+fn shock_cooling_events() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    // the sample flight stays within the usual 50 deg F/min
+    let reports = ShockCoolingAnalyzer::default().analyze(&log.data, &phases);
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].events.is_empty());
+    assert!((reports[0].min_rate.unwrap() + 47.4).abs() < 0.1);
+
+    let analyzer = ShockCoolingAnalyzer {
+        threshold: -40.0,
+        ..Default::default()
+    };
+    let reports = analyzer.analyze(&log.data, &phases);
+    assert_eq!(reports[0].events.len(), 1);
+    let event = &reports[0].events[0];
+    assert_eq!(event.cylinder, 3);
+    assert_eq!(event.start_time.unwrap().format("%H:%M:%S").to_string(), "13:44:41");
+    // the throttle was pulled back leading into the event
+    assert!(event.map_reduction().unwrap() > 10.0);
+    assert!(event.avg_vertical_speed.unwrap() < 0.0);
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use hangar::avionics::AvionicsLog;
 use hangar::garmin;
 use hangar::resource_path;
 
@@ -281,3 +282,41 @@ fn read_eis_data() -> Result<(), String> {
 
     Ok(())
 }
+
+#[test]
+fn read_eis_data_in_batches() -> Result<(), String> {
+    let path = resource_path(SAMPLE_CSV);
+    let eis = garmin::GarminEISLog::from_csv(&path).map_err(|e| e.to_string())?;
+    let reader = garmin::GarminEISLogReader::open(&path, garmin::LogCheckAction::Drop)
+        .map_err(|e| e.to_string())?
+        .with_batch_rows(1000);
+    let batches = reader.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+    // the last batch holds the rest of the rows, with the same columns as the whole log
+    let heights = batches.iter().map(|b| b.height()).collect::<Vec<_>>();
+    assert_eq!(heights, vec![1000, 1000, 1000, 676]);
+    assert_eq!(batches[3].schema(), eis.data.schema());
+    assert!(batches[3].equals_missing(&eis.data.slice(3000, 676)));
+    Ok(())
+}
+
+#[test]
+fn parse_log_file_name() -> Result<(), String> {
+    let path = resource_path(SAMPLE_CSV);
+    let name = garmin::GarminLogFileName::parse(&path).unwrap();
+    assert_eq!(name.start.to_string(), "2023-11-04 08:48:13");
+    assert_eq!(name.airport.as_deref(), Some("KPOU"));
+    assert!(garmin::GarminLogFileName::parse(std::path::Path::new("avidyne_231104.csv")).is_none());
+
+    // the name agrees with the flightstream header, and with the first row at a UTC offset of -4 hours
+    let eis = garmin::GarminEISLog::from_csv(&path).map_err(|e| e.to_string())?;
+    assert!(name.check(&eis).is_empty());
+    let log = AvionicsLog::from_garmin_file(eis, &path);
+    assert_eq!(log.departure_airport.as_deref(), Some("KPOU"));
+
+    // a log renamed to start 20 minutes later disagrees with both
+    let renamed = garmin::GarminLogFileName::parse(std::path::Path::new("log_231104_090813_KPOU.csv")).unwrap();
+    let eis = garmin::GarminEISLog::from_csv(&path).map_err(|e| e.to_string())?;
+    assert_eq!(renamed.check(&eis).len(), 2);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use hangar::avionics::{detect_source, AvionicsLogSource};
+use hangar::ei::{EIEngineLog, EILogHeader};
+use hangar::resource_path;
+
+// An MVP-50P log from a Mooney M20J, recorded in local time
+const MVP50_CSV: &str = "mvp50_231104.csv";
+// A CGR-30P log from a six cylinder engine, recorded in metric units
+const CGR30_CSV: &str = "cgr30_231104.csv";
+
+#[test]
+fn read_header() -> Result<(), String> {
+    let header = EILogHeader::from_csv(&resource_path(MVP50_CSV)).map_err(|e| e.to_string())?;
+    assert_eq!(header.model(), Some("MVP-50P"));
+    assert_eq!(header.tail_number(), Some("N4521J"));
+    assert_eq!(header.table_offset, 5);
+    assert_eq!(header.columns[7].name, "OIL T");
+    assert_eq!(header.columns[7].unit, "F");
+    Ok(())
+}
+
+#[test]
+fn read_mvp50() -> Result<(), String> {
+    let log = EIEngineLog::from_csv(&resource_path(MVP50_CSV)).map_err(|e| e.to_string())?;
+    assert_eq!(log.data.height(), 8);
+
+    let columns = log.data.get_column_names().iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        vec![
+            "Hobbs", "FltTime", "E1 RPM", "E1 MAP", "E1 FFlow", "E1 OilT", "E1 OilP", "E1 FPres", "E1 CHT1", "E1 CHT2",
+            "E1 CHT3", "E1 CHT4", "E1 EGT1", "E1 EGT2", "E1 EGT3", "E1 EGT4", "OAT", "bus1volts", "alt1amps", "FQtyL",
+            "FQtyR", "Timestamp"
+        ]
+    );
+
+    // the outside air temperature is converted into the Garmin unit of Celsius
+    let oat = log.data.column("OAT").unwrap().f64().unwrap().clone();
+    assert!((oat.get(0).unwrap() - 5.0).abs() < 1e-9);
+
+    // local times are converted to UTC using the recorded offset
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let first = timestamps.as_datetime_iter().next().flatten().unwrap();
+    assert_eq!(first.to_string(), "2023-11-04 12:48:13");
+
+    let units = log.units();
+    assert_eq!(units["E1 CHT1"], "deg F");
+    assert_eq!(units["E1 FFlow"], "gph");
+    assert_eq!(units["Hobbs"], "h");
+    assert!(log.warnings.is_empty());
+
+    // units are spelled differently by other software, and a column in a unit that is not known is skipped
+    let text = std::fs::read_to_string(resource_path(MVP50_CSV)).map_err(|e| e.to_string())?;
+    let text = text.replacen(",GPH,F,PSI,PSI,", ",GPH,°F,PSI,KG,", 1);
+    let respelled = EIEngineLog::from_bytes(text.into_bytes()).map_err(|e| e.to_string())?;
+    let oil_temperature = |log: &EIEngineLog| log.data.column("E1 OilT").unwrap().clone();
+    assert!(oil_temperature(&respelled).equals(&oil_temperature(&log)));
+    assert!(respelled.data.column("E1 FPres").is_err());
+    assert_eq!(respelled.warnings.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn read_cgr30() -> Result<(), String> {
+    let log = EIEngineLog::from_csv(&resource_path(CGR30_CSV)).map_err(|e| e.to_string())?;
+    assert_eq!(log.data.height(), 5);
+
+    // column names are matched regardless of spacing and periods, and metric units are converted
+    let cht = log.data.column("E1 CHT6").unwrap().f64().unwrap().clone();
+    assert!((cht.get(0).unwrap() - 365.0).abs() < 1e-9);
+    let fuel_flow = log.data.column("E1 FFlow").unwrap().f64().unwrap().clone();
+    assert!((fuel_flow.get(0).unwrap() - 13.2086).abs() < 1e-3);
+    let oil_pressure = log.data.column("E1 OilP").unwrap().f64().unwrap().clone();
+    assert!((oil_pressure.get(0).unwrap() - 60.916).abs() < 1e-2);
+    let manifold_pressure = log.data.column("E1 MAP").unwrap().f64().unwrap().clone();
+    assert_eq!(manifold_pressure.get(0), Some(25.0));
+    Ok(())
+}
+
+#[test]
+fn detect_log() {
+    let source = detect_source(&resource_path(MVP50_CSV)).unwrap();
+    assert!(matches!(source, Some(AvionicsLogSource::ElectronicsInternational(_))));
+
+    // engine monitors have no flight path to export
+    let source = source.unwrap();
+    assert!(source.to_fdr4("aircraft".to_string(), None).is_err());
+}
@@ -0,0 +1,54 @@
+use hangar::analysis::exceedance::{fdr_fields, AircraftLimits, Bound};
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn sample_exceedances() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let limits = AircraftLimits::from_file(&resource_path("m20j_limits.toml"))?;
+    let exceedances = limits.check(&log.data);
+
+    // shock cooling in the descent
+    let cooling = exceedances.last().unwrap();
+    assert_eq!(cooling.column, "E1 CHT CLD");
+    assert_eq!(cooling.bound, Bound::Below);
+    assert_eq!(cooling.peak, -57.0);
+    assert_eq!(cooling.duration, 15.0);
+    assert_eq!(cooling.start_time.unwrap().format("%H:%M:%S").to_string(), "13:44:43");
+
+    // the cylinders stay well below redline
+    assert!(!exceedances
+        .iter()
+        .any(|e| e.column.starts_with("E1 CHT") && e.bound == Bound::Above));
+
+    let fields = fdr_fields(&exceedances, &log.data);
+    assert_eq!(fields.len(), exceedances.len() * 2);
+    assert_eq!(fields[0].field_name(), "EVNT");
+    assert_eq!(fields[1].field_name(), "MARK");
+    Ok(())
+}
+
+#[test]
+fn cylinder_patterns_and_durations() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+
+    // the pattern matches numbered cylinders only, not E1 CHT CLD
+    let limits = AircraftLimits::from_toml("[[limits]]\ncolumn = \"E1 CHT*\"\nmax = 380.0\n")?;
+    let exceedances = limits.check(&log.data);
+    assert!(!exceedances.is_empty());
+    assert!(exceedances.iter().all(|e| e.column == "E1 CHT4" && e.peak > 380.0));
+
+    let limits = AircraftLimits::from_toml("[[limits]]\ncolumn = \"E1 CHT*\"\nmax = 380.0\nmin_duration = 3600.0\n")?;
+    assert!(limits.check(&log.data).is_empty());
+    Ok(())
+}
+
+#[test]
+fn invalid_limits() {
+    assert!(AircraftLimits::from_toml("[[limits]]\ncolumn = \"E1 RPM\"\n").is_err());
+    assert!(AircraftLimits::from_toml("[[limits]]\ncolumn = \"E1 RPM\"\nmin = 10.0\nmax = 5.0\n").is_err());
+    assert!(AircraftLimits::from_toml("[[limits]]\ncolumn = \"E1 RPM\"\nmaximum = 2700.0\n").is_err());
+}
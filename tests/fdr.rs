@@ -0,0 +1,99 @@
+use chrono::{NaiveDate, TimeDelta};
+use hangar::fdr::{CommentField, FDRFileVersion4, FDRWriter};
+use polars::prelude::*;
+
+/// Flight data with the given times, in ms after 23:59:59
+fn flight_data(millis: &[i64]) -> DataFrame {
+    let start = NaiveDate::from_ymd_opt(2023, 11, 4)
+        .unwrap()
+        .and_hms_opt(23, 59, 59)
+        .unwrap();
+    let n = millis.len();
+    DataFrame::new(vec![
+        Column::new(
+            "Timestamp".into(),
+            millis
+                .iter()
+                .map(|&ms| start + TimeDelta::milliseconds(ms))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("Longitude".into(), vec![-73.9; n]),
+        Column::new("Latitude".into(), vec![41.6; n]),
+        Column::new("AltB".into(), vec![1000.0; n]),
+        Column::new("HDG".into(), vec![90.0; n]),
+        Column::new("Pitch".into(), vec![0.0; n]),
+        Column::new("Roll".into(), (0..n).map(|i| i as f64).collect::<Vec<_>>()),
+    ])
+    .unwrap()
+}
+
+/// Write flight data with the given times, in ms after 23:59:59, returning the times of the data lines
+fn written_times(name: &str, millis: &[i64]) -> std::io::Result<Vec<String>> {
+    let data = flight_data(millis);
+    let path = std::env::temp_dir().join(format!("hangar_test_{}.fdr", name));
+    FDRFileVersion4::new(data, None).write_fdr(&Some(path.clone()))?;
+    let text = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(text
+        .lines()
+        .skip(2)
+        .map(|line| line.split(',').next().unwrap().to_string())
+        .collect())
+}
+
+#[test]
+fn write_whole_and_fractional_seconds() -> std::io::Result<()> {
+    // whole seconds through midnight, with a duplicate second
+    let times = written_times("whole", &[0, 1000, 1000, 2000])?;
+    assert_eq!(times, ["23:59:59", "24:00:00", "24:00:01"]);
+
+    let times = written_times("fractional", &[0, 200, 400, 1000, 1200])?;
+    assert_eq!(
+        times,
+        [
+            "23:59:59.000",
+            "23:59:59.200",
+            "23:59:59.400",
+            "24:00:00.000",
+            "24:00:00.200"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn write_times_after_clock_correction() -> std::io::Result<()> {
+    // the clock is corrected back by two seconds after the third row
+    let times = written_times("corrected", &[0, 1000, 2000, 1000, 2000, 3000])?;
+    assert_eq!(times, ["23:59:59", "24:00:00", "24:00:01", "24:00:02"]);
+
+    // a row near the end is recorded at midnight of the first day, as after a GPS reset
+    let times = written_times("reset", &[0, 1000, 2000, 3000, -86_399_000, 4000])?;
+    assert_eq!(times, ["23:59:59", "24:00:00", "24:00:01", "24:00:02", "24:00:03"]);
+    Ok(())
+}
+
+#[test]
+fn write_to_memory() -> std::io::Result<()> {
+    let mut fdr = FDRFileVersion4::new(flight_data(&[0, 1000]), None);
+    fdr.add_field(Box::new(CommentField {
+        comment: "written in memory".to_string(),
+    }));
+
+    let text = fdr.to_fdr_string()?;
+    assert_eq!(text.lines().take(3).collect::<Vec<_>>(), ["A", "4", "COMM,written in memory"]);
+    assert_eq!(text.lines().count(), 5);
+    assert!(text.lines().nth(3).unwrap().starts_with("23:59:59,-73.9,41.6,"));
+
+    // writing to any writer gives the same output as a file
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    fdr.write_to(&mut buffer)?;
+    assert_eq!(buffer.into_inner(), text.as_bytes());
+
+    let path = std::env::temp_dir().join("hangar_test_memory.fdr");
+    fdr.write_fdr(&Some(path.clone()))?;
+    let written = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(written, text);
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use hangar::analysis::fuel::FuelAnalyzer;
+use hangar::analysis::phase::{FlightPhase, PhaseClassifier};
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn reconcile_sample_fuel() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    let report = FuelAnalyzer::default().analyze(&log.data, &phases);
+    let (flow, quantity) = (report.used_flow.unwrap(), report.used_quantity.unwrap());
+    assert!((7.0..8.5).contains(&flow));
+    assert!((flow - quantity).abs() < 0.5);
+    assert_eq!(report.disagreement, Some(false));
+
+    // the flight was flown on the left tank
+    assert!(report.used_left.unwrap() > 7.0);
+    assert!(report.used_right.unwrap().abs() < 1.0);
+
+    // the cruise burned the most, at a higher rate than the taxi
+    let burn = |phase| report.phases.iter().find(|b| b.phase == phase).unwrap();
+    let cruise = burn(FlightPhase::Cruise);
+    assert!(report.phases.iter().all(|b| b.fuel <= cruise.fuel));
+    assert!(cruise.rate.unwrap() > burn(FlightPhase::TaxiOut).rate.unwrap());
+    let total = report.phases.iter().map(|b| b.fuel).sum::<f64>();
+    assert!((total - flow).abs() < 0.01);
+
+    // a tight tolerance flags the small difference
+    let strict = FuelAnalyzer {
+        tolerance: 0.001,
+        ..Default::default()
+    };
+    assert_eq!(strict.analyze(&log.data, &phases).disagreement, Some(true));
+    Ok(())
+}
+
+#[test]
+fn endurance_falls_in_cruise() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+    let analyzer = FuelAnalyzer::default();
+
+    // 48 gals at about 9.5 gph in cruise
+    let cruise = phases.segments.iter().find(|s| s.phase == FlightPhase::Cruise).unwrap();
+    let endurance = analyzer.endurance(&log.data);
+    let (start, end) = (endurance[cruise.start].unwrap(), endurance[cruise.end - 1].unwrap());
+    assert!((4.0..6.0).contains(&start));
+    assert!(end < start);
+
+    let with_reserve = FuelAnalyzer {
+        reserve: 9.5,
+        ..Default::default()
+    };
+    assert!((with_reserve.endurance(&log.data)[cruise.start].unwrap() - (start - 1.0)).abs() < 0.2);
+
+    let df = analyzer.with_endurance(log.data).map_err(|e| e.to_string())?;
+    assert!(df.column("Endurance").is_ok());
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use chrono::NaiveDate;
+use hangar::avionics::{detect_source, AvionicsLogSource};
+use hangar::gdl90::{self, Gdl90Log, Gdl90Message};
+use hangar::resource_path;
+
+// A synthetic Stratux capture of a departure, recorded across midnight UTC
+const SAMPLE_CAPTURE: &str = "stratux_231104.gdl90";
+
+#[test]
+fn heartbeat_crc_matches_specification_example() {
+    let message = [0x00, 0x81, 0x41, 0xDB, 0xD0, 0x08, 0x02];
+    assert_eq!(gdl90::crc16(&message), 0x8BB3);
+
+    let frame = gdl90::encode_frame(&message);
+    assert_eq!(frame, vec![0x7E, 0x00, 0x81, 0x41, 0xDB, 0xD0, 0x08, 0x02, 0xB3, 0x8B, 0x7E]);
+}
+
+#[test]
+fn decode_traffic_report_specification_example() {
+    let message = [
+        0x14, 0x00, 0xAB, 0x45, 0x49, 0x1F, 0xEF, 0x15, 0xA8, 0x89, 0x78, 0x0F, 0x09, 0xA9, 0x07, 0xB0, 0x01, 0x20,
+        0x01, 0x4E, 0x38, 0x32, 0x35, 0x56, 0x20, 0x20, 0x20, 0x00,
+    ];
+    let report = match Gdl90Message::decode(&message) {
+        Some(Gdl90Message::TrafficReport(report)) => report,
+        other => panic!("unexpected message {:?}", other),
+    };
+    assert_eq!(report.address, 0xAB4549);
+    assert!((report.latitude.unwrap() - 44.90708).abs() < 1e-4);
+    assert!((report.longitude.unwrap() + 122.99488).abs() < 1e-4);
+    assert_eq!(report.pressure_altitude, Some(5000.0));
+    assert!(report.airborne);
+    assert_eq!((report.nic, report.nacp), (10, 9));
+    assert_eq!(report.horizontal_velocity, Some(123.0));
+    assert_eq!(report.vertical_velocity, Some(64.0));
+    assert_eq!(report.track, Some(45.0));
+    assert_eq!(report.callsign, "N825V");
+}
+
+#[test]
+fn unframe_removes_escapes_and_rejects_bad_crc() {
+    // the payload holds both bytes that must be escaped
+    let message = [0x65, 0x7E, 0x7D, 0x00];
+    let mut stream = gdl90::encode_frame(&message);
+    let mut corrupted = gdl90::encode_frame(&[0x00, 0x81, 0x41, 0xDB, 0xD0, 0x08, 0x02]);
+    corrupted[3] ^= 0x01;
+    stream.extend(corrupted);
+
+    let mut stats = gdl90::FrameStats::default();
+    let messages = gdl90::unframe(&stream, &mut stats);
+    assert_eq!(messages, vec![message.to_vec()]);
+    assert_eq!(stats.frames, 1);
+    assert_eq!(stats.crc_errors, 1);
+}
+
+#[test]
+fn read_capture() -> Result<(), String> {
+    let bytes = std::fs::read(resource_path(SAMPLE_CAPTURE)).map_err(|e| e.to_string())?;
+    let date = NaiveDate::from_ymd_opt(2023, 11, 4).unwrap();
+    let log = Gdl90Log::from_bytes(&bytes, date).map_err(|e| e.to_string())?;
+
+    assert_eq!(log.data.height(), 55);
+    assert_eq!(log.traffic.height(), 5);
+    assert_eq!(log.stats.crc_errors, 1);
+    assert_eq!(log.stats.unknown_messages, 1);
+
+    // the capture crosses midnight, so the date must advance
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let first = timestamps.as_datetime_iter().next().flatten().unwrap();
+    let last = timestamps.as_datetime_iter().last().flatten().unwrap();
+    assert_eq!(first.to_string(), "2023-11-04 23:59:35");
+    assert_eq!(last.to_string(), "2023-11-05 00:00:29");
+
+    // dated by the time the recording ended, after midnight, the capture starts the day before
+    let end = NaiveDate::from_ymd_opt(2023, 11, 5)
+        .unwrap()
+        .and_hms_opt(0, 0, 40)
+        .unwrap()
+        .and_utc();
+    let ended = Gdl90Log::ending_at(&bytes, end).map_err(|e| e.to_string())?;
+    assert!(ended.data.equals_missing(&log.data));
+
+    // attitude is wings level until the AHRS reports, then uses the AHRS values
+    let roll = log.data.column("Roll").unwrap().f64().unwrap().clone();
+    assert_eq!(roll.get(0), Some(0.0));
+    assert_eq!(roll.get(54), Some(5.0));
+    let altitude = log.data.column("AltGPS").unwrap().f64().unwrap().clone();
+    assert_eq!(altitude.get(0), Some(250.0));
+    Ok(())
+}
+
+#[test]
+fn detect_capture() {
+    let source = detect_source(&resource_path(SAMPLE_CAPTURE)).unwrap();
+    assert!(matches!(source, Some(AvionicsLogSource::Gdl90(_))));
+
+    let source = detect_source(&resource_path("log_231104_084813_KPOU.csv")).unwrap();
+    assert!(matches!(source, Some(AvionicsLogSource::Garmin(_))));
+}
@@ -0,0 +1,13 @@
+use hangar::geo::Position;
+
+#[test]
+fn distance_and_bearing() {
+    // Poughkeepsie (KPOU) to Albany (KALB)
+    let kpou = Position::new(41.6266, -73.8842);
+    let kalb = Position::new(42.7483, -73.8017);
+
+    assert!((kpou.distance_nm(&kalb) - 67.4).abs() < 0.5);
+    assert!((kpou.bearing_deg(&kalb) - 3.0).abs() < 1.0);
+    assert!((kalb.bearing_deg(&kpou) - 183.0).abs() < 1.0);
+    assert_eq!(kpou.distance_nm(&kpou), 0.0);
+}
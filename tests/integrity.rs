@@ -0,0 +1,140 @@
+use chrono::{NaiveDate, TimeDelta};
+use hangar::avionics::AvionicsLogSource;
+use hangar::data::column_f64;
+use hangar::integrity::{IntegrityAction, IntegrityChecker, IntegrityLossReason};
+use hangar::nmea::NmeaLog;
+use hangar::resource_path;
+use polars::prelude::*;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+// A synthetic handheld GPS log of a climb-out
+const NMEA_LOG: &str = "gps_231104.nmea";
+
+/// Acquiring a fix, then losing the WAAS HPL for two rows and the vertical limit on an approach for one
+fn degraded_log() -> PolarsResult<DataFrame> {
+    let start = NaiveDate::from_ymd_opt(2023, 11, 4)
+        .unwrap()
+        .and_hms_opt(14, 0, 0)
+        .unwrap();
+    let n = 8;
+    DataFrame::new(vec![
+        Column::new(
+            "Timestamp".into(),
+            (0..n).map(|s| start + TimeDelta::seconds(s)).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "Latitude".into(),
+            (0..n).map(|r| 41.6 + r as f64 * 0.001).collect::<Vec<_>>(),
+        ),
+        Column::new("Longitude".into(), vec![-73.9; n as usize]),
+        Column::new(
+            "GPSfix".into(),
+            ["NoSoln", "2D", "3D", "3DDiff", "3DDiff", "3DDiff", "3DDiff", "3DDiff"],
+        ),
+        Column::new("HAL".into(), [0.0, 0.0, 3704.0, 1852.0, 1852.0, 1852.0, 556.0, 556.0]),
+        Column::new(
+            "VAL".into(),
+            [None, None, None, None, None, None, Some(50.0), Some(50.0)],
+        ),
+        Column::new(
+            "HPLwas".into(),
+            [
+                None,
+                None,
+                None,
+                Some(20.0),
+                Some(2000.0),
+                Some(1900.0),
+                Some(20.0),
+                Some(20.0),
+            ],
+        ),
+        Column::new(
+            "HPLfd".into(),
+            [
+                None,
+                None,
+                Some(800.0),
+                Some(20.0),
+                Some(20.0),
+                Some(20.0),
+                Some(20.0),
+                Some(20.0),
+            ],
+        ),
+        Column::new(
+            "VPLwas".into(),
+            [None, None, None, Some(90.0), None, None, Some(30.0), Some(60.0)],
+        ),
+    ])
+}
+
+#[test]
+fn check_degraded_fixes() -> PolarsResult<()> {
+    let df = degraded_log()?;
+    let checker = IntegrityChecker::default();
+    let report = checker.check(&df);
+    assert_eq!(report.valid_rows, 3);
+    assert_eq!(report.first_valid, Some(2));
+    let losses = report
+        .losses
+        .iter()
+        .map(|l| (l.reason, l.start, l.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        losses,
+        [
+            (IntegrityLossReason::Fix, 0, 2),
+            (IntegrityLossReason::Horizontal, 4, 6),
+            (IntegrityLossReason::Vertical, 7, 8),
+        ]
+    );
+    assert_eq!(report.interruptions().count(), 2);
+
+    // dropping clears the positions without integrity
+    let dropped = checker.apply(df.clone(), IntegrityAction::Drop)?;
+    let latitude = column_f64(&dropped, "Latitude");
+    let kept = latitude.iter().map(|l| l.is_some()).collect::<Vec<_>>();
+    assert_eq!(kept, [false, false, true, true, false, false, true, false]);
+
+    let marked = checker.apply(df, IntegrityAction::Mark)?;
+    assert_eq!(marked.column("GPSValid")?.bool()?.sum(), Some(3));
+    assert_eq!(column_f64(&marked, "Latitude").iter().flatten().count(), 8);
+    Ok(())
+}
+
+#[test]
+fn check_sample_log() {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read().unwrap();
+    let report = IntegrityChecker::default().check(&log.data);
+
+    // the GPS acquires a fix 149 rows in and keeps its integrity to the end
+    assert_eq!(report.losses.len(), 1);
+    assert_eq!(report.first_valid, Some(149));
+    assert_eq!(report.valid_rows, 3676 - 149);
+    assert_eq!(report.interruptions().count(), 0);
+}
+
+#[test]
+fn check_nmea_log_without_fixes() -> PolarsResult<()> {
+    // a handheld GPS that sends RMC, VTG and PGRMZ but not GGA records no fix
+    let text = std::fs::read_to_string(resource_path(NMEA_LOG))?;
+    let text = text
+        .lines()
+        .filter(|line| !line.contains("GGA"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let log = NmeaLog::from_reader(text.as_bytes())?;
+    let checker = IntegrityChecker::default();
+    let report = checker.check(&log.data);
+    assert_eq!(report.valid_rows, log.data.height());
+    assert!(report.losses.is_empty());
+
+    // the positions are kept
+    let positions = column_f64(&log.data, "Latitude").iter().flatten().count();
+    assert!(positions > 0);
+    let dropped = checker.apply(log.data, IntegrityAction::Drop)?;
+    assert_eq!(column_f64(&dropped, "Latitude").iter().flatten().count(), positions);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use hangar::analysis::landing::{self, LandingAnalyzer};
+use hangar::analysis::phase::PhaseClassifier;
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn measure_sample_landing() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    let landings = LandingAnalyzer::default().analyze(&log.data, &phases);
+    assert_eq!(landings.len(), 1);
+    let landing = &landings[0];
+
+    // OnGrnd is set well after the wheels touch, once the airspeed has decayed
+    assert_eq!(landing.on_ground, 3457);
+    assert!((3430..3450).contains(&landing.touchdown));
+    assert!(landing.airspeed.unwrap() > 45.0 && landing.airspeed.unwrap() < 65.0);
+    assert!(landing.vertical_speed.unwrap() > -500.0 && landing.vertical_speed.unwrap() < 0.0);
+    assert!((landing.load_factor.unwrap() - 1.0).abs() < 0.2);
+    assert!(landing.float_time.unwrap() > 0.0 && landing.float_distance.unwrap() > 0.0);
+    assert!(landing.crab.unwrap().abs() < 10.0);
+
+    // a southerly wind across runway 24
+    assert!(landing.crosswind.unwrap() < 0.0);
+
+    let fields = landing::fdr_fields(&landings, &log.data);
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0].field_name(), "EVNT");
+    Ok(())
+}
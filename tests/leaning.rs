@@ -0,0 +1,84 @@
+use chrono::{NaiveDate, TimeDelta};
+use hangar::analysis::leaning::{peaks_dataframe, LeaningAnalyzer};
+use hangar::analysis::phase::PhaseClassifier;
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+use polars::prelude::*;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+/// The fuel flow at which each cylinder of the synthetic engine peaks
+const PEAK_FLOWS: [f64; 4] = [9.8, 9.6, 9.9, 9.5];
+
+/// Ten minutes of cruise, leaning from 11 to 8 gph in five minutes after a minute of steady cruise
+fn leaning_cruise() -> PolarsResult<DataFrame> {
+    let rows = 600;
+    let flow = (0..rows)
+        .map(|i| match i {
+            0..60 => 11.0,
+            60..360 => 11.0 - 3.0 * (i - 60) as f64 / 300.0,
+            _ => 8.0,
+        })
+        .collect::<Vec<_>>();
+    let start = NaiveDate::from_ymd_opt(2023, 11, 4)
+        .unwrap()
+        .and_hms_opt(14, 0, 0)
+        .unwrap();
+    let mut columns = vec![
+        Column::new(
+            "Timestamp".into(),
+            (0..rows).map(|i| start + TimeDelta::seconds(i)).collect::<Vec<_>>(),
+        ),
+        Column::new("OnGrnd".into(), vec![0i64; rows as usize]),
+        Column::new("AltB".into(), vec![8000.0; rows as usize]),
+        Column::new("VSpd".into(), vec![0.0; rows as usize]),
+        Column::new("E1 MAP".into(), vec![22.0; rows as usize]),
+        Column::new("E1 RPM".into(), vec![2500.0; rows as usize]),
+        Column::new("E1 FFlow".into(), flow.clone()),
+    ];
+    for (i, peak) in PEAK_FLOWS.iter().enumerate() {
+        let egt = flow
+            .iter()
+            .map(|f| 1450.0 - 40.0 * (f - peak).powi(2))
+            .collect::<Vec<_>>();
+        columns.push(Column::new(format!("E1 EGT{}", i + 1).into(), egt));
+    }
+    DataFrame::new(columns)
+}
+
+#[test]
+fn find_sweep_and_spread() -> PolarsResult<()> {
+    let data = leaning_cruise()?;
+    let phases = PhaseClassifier::default().classify(&data);
+    let sweeps = LeaningAnalyzer::default().find_sweeps(&data, &phases);
+    assert_eq!(sweeps.len(), 1);
+
+    let sweep = &sweeps[0];
+    assert!(sweep.missing.is_empty());
+    for (peak, expected) in sweep.peaks.iter().zip(PEAK_FLOWS) {
+        assert!((peak.fuel_flow - expected).abs() < 0.05);
+    }
+    assert!((sweep.spread().unwrap() - 0.4).abs() < 0.05);
+    assert_eq!(sweep.first_to_peak(), Some(3));
+
+    // the steady cruise before and after the sweep is left out
+    assert!((60..=80).contains(&sweep.start));
+    assert!((355..=365).contains(&sweep.end));
+
+    let chart = sweep.chart_data(&data)?;
+    assert_eq!(chart.height(), sweep.end - sweep.start);
+    assert_eq!(chart.width(), 6);
+    let table = peaks_dataframe(&sweeps)?;
+    assert_eq!(table.height(), 4);
+    Ok(())
+}
+
+#[test]
+fn no_sweep_in_sample() -> Result<(), String> {
+    // the sample flight changes power in cruise, but is never leaned past peak
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+    assert!(LeaningAnalyzer::default().find_sweeps(&log.data, &phases).is_empty());
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use hangar::garmin::{log_check, GarminEISLog, LogCheckAction};
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+/// A copy of the sample log with a digit changed in the altitude of the rows at the given lines, as by a bad SD card
+fn corrupted_log(name: &str, lines: &[usize]) -> std::io::Result<std::path::PathBuf> {
+    let text = std::fs::read(resource_path(SAMPLE_CSV))?;
+    let mut rows = text.split(|&b| b == b'\n').map(|l| l.to_vec()).collect::<Vec<_>>();
+    for &line in lines {
+        let row = &mut rows[line - 1];
+        let digit = row.iter().position(|b| b.is_ascii_digit()).unwrap();
+        row[digit] = if row[digit] == b'9' { b'8' } else { row[digit] + 1 };
+    }
+    let path = std::env::temp_dir().join(format!("hangar_test_{}.csv", name));
+    std::fs::write(&path, rows.join(&b'\n'))?;
+    Ok(path)
+}
+
+#[test]
+fn verify_sample_log_check() -> Result<(), String> {
+    // the check value of CRC-16/CCITT-FALSE
+    assert_eq!(log_check(b"123456789"), 0x29b1);
+
+    let log = GarminEISLog::from_csv(&resource_path(SAMPLE_CSV)).map_err(|e| e.to_string())?;
+    let report = log.log_check.unwrap();
+    assert_eq!(report.rows, 3676);
+    assert!(report.failed.is_empty());
+    assert_eq!(log.data.height(), 3676);
+    Ok(())
+}
+
+#[test]
+fn drop_or_mark_corrupted_rows() -> Result<(), String> {
+    let path = corrupted_log("logcheck", &[500, 2001]).map_err(|e| e.to_string())?;
+
+    let log = GarminEISLog::from_csv(&path).map_err(|e| e.to_string())?;
+    let report = log.log_check.unwrap();
+    assert_eq!(report.failed.iter().map(|r| r.line).collect::<Vec<_>>(), [500, 2001]);
+    assert!(report.failed.iter().all(|r| r.recorded.is_some()));
+    assert_eq!(log.data.height(), 3674);
+    assert_eq!(
+        report.to_string(),
+        "2 of 3676 rows failed the LogCheck and were dropped, at lines 500, 2001"
+    );
+
+    let log = GarminEISLog::from_csv_with_log_check(&path, LogCheckAction::Mark).map_err(|e| e.to_string())?;
+    assert_eq!(log.data.height(), 3676);
+    let valid = log.data.column("LogCheckValid").unwrap().bool().unwrap().clone();
+    assert_eq!(valid.sum(), Some(3674));
+    assert_eq!(valid.get(500 - 4), Some(false));
+
+    let log = GarminEISLog::from_csv_with_log_check(&path, LogCheckAction::Ignore).map_err(|e| e.to_string())?;
+    assert!(log.log_check.is_none());
+    assert_eq!(log.data.height(), 3676);
+    std::fs::remove_file(path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,98 @@
+use hangar::mapping::{CsvMapping, MappedCsvLog};
+use hangar::resource_path;
+use hangar::units;
+
+// A CSV export from another avionics product, with its mapping file
+const SAMPLE_CSV: &str = "efis_export.csv";
+const SAMPLE_MAPPING: &str = "efis_export.toml";
+
+#[test]
+fn convert_units() {
+    let (scale, offset) = units::conversion("m", "ft").unwrap();
+    assert!((100.0 * scale + offset - 328.084).abs() < 1e-3);
+    let (scale, offset) = units::conversion("deg F", "deg C").unwrap();
+    assert!((212.0 * scale + offset - 100.0).abs() < 1e-9);
+    assert!(units::conversion("kt", "psi").is_none());
+}
+
+#[test]
+fn reject_invalid_mapping() {
+    let missing_time = r#"
+        [timestamp]
+        date_column = "Date"
+        format = "%Y-%m-%d"
+
+        [[columns]]
+        source = "Lat"
+        field = "Latitude"
+    "#;
+    assert!(CsvMapping::from_toml(missing_time).is_err());
+
+    let wrong_unit = r#"
+        [timestamp]
+        column = "Time"
+        format = "%Y-%m-%d %H:%M:%S"
+
+        [[columns]]
+        source = "Alt"
+        field = "AltB"
+        unit = "psi"
+    "#;
+    assert!(CsvMapping::from_toml(wrong_unit).is_err());
+}
+
+#[test]
+fn read_mapped_csv() -> Result<(), String> {
+    let mapping = CsvMapping::from_file(&resource_path(SAMPLE_MAPPING))?;
+    let log = MappedCsvLog::from_csv(&resource_path(SAMPLE_CSV), &mapping).map_err(|e| e.to_string())?;
+
+    let columns = log.data.get_column_names().iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        vec!["Latitude", "Longitude", "AltB", "GndSpd", "HDG", "Pitch", "Roll", "OAT", "AtvWpt", "Timestamp"]
+    );
+    // the trailing row without a time is dropped
+    assert_eq!(log.data.height(), 10);
+
+    // local times are converted to UTC
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let first = timestamps.as_datetime_iter().next().flatten().unwrap();
+    assert_eq!(first.to_string(), "2023-11-04 12:50:00");
+
+    let altitude = log.data.column("AltB").unwrap().f64().unwrap().clone();
+    assert!((altitude.get(0).unwrap() - 328.084).abs() < 1e-3);
+    let speed = log.data.column("GndSpd").unwrap().f64().unwrap().clone();
+    assert!((speed.get(0).unwrap() - 80.994).abs() < 1e-3);
+    let oat = log.data.column("OAT").unwrap().f64().unwrap().clone();
+    assert!((oat.get(0).unwrap() - 10.0).abs() < 1e-9);
+    let waypoint = log.data.column("AtvWpt").unwrap().str().unwrap().clone();
+    assert_eq!(waypoint.get(9), Some("KSWF"));
+
+    // around the end of daylight saving time, a time in the repeated hour is the earlier one, and a malformed time or
+    // one in the skipped hour of the spring drops only its row
+    let text = "Time,Alt\n2023-11-05 00:59:59,100\n2023-11-05 01:30:00,200\n2023-11-05 1:3x,300\n\
+                2024-03-10 02:30:00,400\n2023-11-05 02:00:00,500\n";
+    let mapping = CsvMapping::from_toml(
+        r#"
+        [timestamp]
+        column = "Time"
+        format = "%Y-%m-%d %H:%M:%S"
+        timezone = "America/New_York"
+
+        [[columns]]
+        source = "Alt"
+        field = "AltB"
+    "#,
+    )?;
+    let log = MappedCsvLog::from_bytes(text.as_bytes().to_vec(), &mapping).map_err(|e| e.to_string())?;
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let times = timestamps
+        .as_datetime_iter()
+        .map(|t| t.unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        ["2023-11-05 04:59:59", "2023-11-05 05:30:00", "2023-11-05 07:00:00"]
+    );
+    Ok(())
+}
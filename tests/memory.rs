@@ -0,0 +1,79 @@
+use hangar::{garmin, resource_path};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the bytes allocated, to measure the peak memory of reading a log
+struct PeakAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            let allocated = ALLOCATED.fetch_add(new_size, Ordering::Relaxed) + new_size;
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAllocator = PeakAllocator;
+
+/// The bytes allocated at most while running a function, above those allocated before it
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed) - before)
+}
+
+/// A long log, made of the rows of the sample repeated
+fn large_log(copies: usize) -> std::io::Result<std::path::PathBuf> {
+    let path = std::env::temp_dir().join("hangar_test_large_log.csv");
+    let sample = std::fs::read(resource_path("log_231104_084813_KPOU.csv"))?;
+    let sample = &sample[..sample.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)];
+    let lines = sample.split_inclusive(|&b| b == b'\n').collect::<Vec<_>>();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    lines[..3].iter().try_for_each(|line| file.write_all(line))?;
+    for _ in 0..copies {
+        lines[3..].iter().try_for_each(|line| file.write_all(line))?;
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+#[test]
+#[ignore = "slow, reads a log of about 20 hours"]
+fn read_batches_in_less_memory() -> Result<(), String> {
+    // reading batches and dropping each holds one batch rather than the whole log
+    let path = large_log(20).map_err(|e| e.to_string())?;
+    let (whole, whole_peak) = peak_allocation(|| garmin::GarminEISLog::from_csv(&path).map(|log| log.data.height()));
+    let (batches, batches_peak) = peak_allocation(|| {
+        garmin::GarminEISLogReader::open(&path, garmin::LogCheckAction::Drop)?
+            .map(|batch| batch.map(|b| b.height()))
+            .sum::<Result<usize, _>>()
+    });
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    assert_eq!(whole.map_err(|e| e.to_string())?, batches.map_err(|e| e.to_string())?);
+    assert!(batches_peak < whole_peak / 4);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use hangar::avionics::{detect_source, AvionicsLogSource};
+use hangar::nmea::{self, NmeaLog};
+use hangar::resource_path;
+
+// A synthetic handheld GPS log of a climb-out, recorded across midnight UTC
+const SAMPLE_LOG: &str = "gps_231104.nmea";
+
+#[test]
+fn parse_sentence_checks_checksum() {
+    let fields = nmea::parse_sentence("$PGRMZ,184,f,3*16").unwrap();
+    assert_eq!(fields, vec!["PGRMZ", "184", "f", "3"]);
+
+    assert!(nmea::parse_sentence("$PGRMZ,185,f,3*16").is_none());
+    assert!(nmea::parse_sentence("not a sentence").is_none());
+}
+
+#[test]
+fn read_log() -> Result<(), String> {
+    let log = NmeaLog::from_file(&resource_path(SAMPLE_LOG)).map_err(|e| e.to_string())?;
+
+    assert_eq!(log.data.height(), 20);
+    assert_eq!(log.stats.checksum_errors, 1);
+    assert_eq!(log.stats.unsupported, 1);
+
+    // the first row has no RMC sentence, so it is dated from the RMC that follows it
+    let timestamps = log.data.column("Timestamp").unwrap().datetime().unwrap().clone();
+    let dates = timestamps
+        .as_datetime_iter()
+        .map(|t| t.unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(dates[0], "2023-11-04 23:59:50");
+    assert_eq!(dates[9], "2023-11-04 23:59:59");
+    assert_eq!(dates[10], "2023-11-05 00:00:00");
+    assert_eq!(dates[19], "2023-11-05 00:00:09");
+
+    let latitude = log.data.column("Latitude").unwrap().f64().unwrap().clone();
+    assert!((latitude.get(0).unwrap() - 41.627).abs() < 1e-6);
+    let longitude = log.data.column("Longitude").unwrap().f64().unwrap().clone();
+    assert!((longitude.get(0).unwrap() + 73.8842).abs() < 1e-6);
+
+    // the barometric altitude comes from PGRMZ, in feet
+    let altitude = log.data.column("AltB").unwrap().f64().unwrap().clone();
+    assert_eq!(altitude.get(0), Some(184.0));
+    let track = log.data.column("TRK").unwrap().f64().unwrap().clone();
+    assert_eq!(track.get(0), Some(12.5));
+    Ok(())
+}
+
+#[test]
+fn detect_log() {
+    let source = detect_source(&resource_path(SAMPLE_LOG)).unwrap();
+    assert!(matches!(source, Some(AvionicsLogSource::Nmea(_))));
+
+    // binary data that looks like the start of a sentence is not NMEA
+    assert!(!NmeaLog::is_nmea(&String::from_utf8_lossy(b"$A\xff\xfe\xfd\xfc,1,2\n")));
+}
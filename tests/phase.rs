@@ -0,0 +1,72 @@
+use hangar::analysis::phase::{FlightPhase, PhaseClassifier};
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+use polars::prelude::*;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn classify_sample_phases() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+    assert_eq!(phases.phases.len(), log.data.height());
+
+    // the flight starts before the engine is started, and the log ends while taxiing in with the engine running
+    let order = phases.segments.iter().map(|s| s.phase).collect::<Vec<_>>();
+    assert_eq!(order.first(), Some(&FlightPhase::Preflight));
+    assert_eq!(
+        order[1..4],
+        [FlightPhase::TaxiOut, FlightPhase::Takeoff, FlightPhase::InitialClimb]
+    );
+    assert!(order.contains(&FlightPhase::Cruise));
+    assert_eq!(order.last(), Some(&FlightPhase::TaxiIn));
+    assert!(!order.contains(&FlightPhase::Shutdown));
+
+    // OnGrnd changes at rows 896 and 3457
+    assert_eq!(phases.takeoffs(), vec![896]);
+    assert_eq!(phases.landings(), vec![3457]);
+    assert_eq!(phases.phases[3456], FlightPhase::Approach);
+
+    // segments cover every row, in order
+    assert_eq!(phases.segments.first().unwrap().start, 0);
+    assert_eq!(phases.segments.last().unwrap().end, log.data.height());
+    assert!(phases.segments.windows(2).all(|w| w[0].end == w[1].start));
+    Ok(())
+}
+
+#[test]
+fn phase_column_and_markers() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let phases = PhaseClassifier::default().classify(&log.data);
+
+    let data = phases.with_phase_column(log.data.clone()).map_err(|e| e.to_string())?;
+    let column = data.column("Phase").map_err(|e| e.to_string())?;
+    assert_eq!(column.str().unwrap().get(900), Some("Initial climb"));
+
+    let markers = phases.markers(&log.data);
+    assert_eq!(markers.len(), phases.segments.len());
+    assert_eq!(markers[0].time, 0);
+    assert_eq!(markers[3].text, "Initial climb");
+    // one row per second, the takeoff being 9:02:28 and the log starting 8:48:13
+    assert_eq!(markers[3].time, 855);
+    Ok(())
+}
+
+#[test]
+fn classify_touch_and_go() -> Result<(), String> {
+    // two circuits at 100 kt with a touch-and-go between them, never slowing to taxi speed on the runway
+    let speeds = [(50, 10.0), (200, 100.0), (5, 30.0), (200, 100.0), (50, 10.0)]
+        .into_iter()
+        .flat_map(|(rows, speed)| std::iter::repeat_n(speed, rows))
+        .collect::<Vec<f64>>();
+    let data = DataFrame::new(vec![Column::new("GndSpd".into(), speeds)]).map_err(|e| e.to_string())?;
+    let phases = PhaseClassifier::default().classify(&data);
+
+    assert_eq!(phases.takeoffs(), vec![50, 255]);
+    assert_eq!(phases.landings(), vec![250, 455]);
+    assert_eq!(phases.phases[250], FlightPhase::LandingRoll);
+    assert_eq!(phases.phases[254], FlightPhase::Takeoff);
+    assert_eq!(phases.flights(), vec![50..250, 255..455]);
+    Ok(())
+}
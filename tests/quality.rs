@@ -0,0 +1,105 @@
+use chrono::{NaiveDate, TimeDelta};
+use hangar::avionics::AvionicsLogSource;
+use hangar::data::column_f64;
+use hangar::quality::{median_filter, IssueKind, QualityChecker};
+use hangar::resource_path;
+use polars::prelude::*;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+/// Five minutes in the air at 1 Hz, with a spike in the airspeed at row 100, a fix 30 nm off track at row 50, CHT1
+/// frozen for rows 150 to 229 and the oil temperature stuck
+fn glitchy_log() -> PolarsResult<DataFrame> {
+    let start = NaiveDate::from_ymd_opt(2023, 11, 4)
+        .unwrap()
+        .and_hms_opt(14, 0, 0)
+        .unwrap();
+    let rows = 0..300;
+    DataFrame::new(vec![
+        Column::new(
+            "Timestamp".into(),
+            rows.clone().map(|r| start + TimeDelta::seconds(r)).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "Latitude".into(),
+            rows.clone()
+                .map(|r| 41.6 + r as f64 * 0.0005 + if r == 50 { 0.5 } else { 0.0 })
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("Longitude".into(), rows.clone().map(|_| -73.9).collect::<Vec<_>>()),
+        Column::new(
+            "IAS".into(),
+            rows.clone()
+                .map(|r| {
+                    if r == 100 {
+                        300.0
+                    } else {
+                        100.0 + 5.0 * (r as f64 / 10.0).sin()
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "E1 CHT1".into(),
+            rows.clone()
+                .map(|r| match r {
+                    150..230 => 360.0,
+                    _ => 350.0 + 20.0 * (r as f64 / 20.0).sin(),
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("E1 OilT".into(), rows.clone().map(|_| 180.0).collect::<Vec<_>>()),
+    ])
+}
+
+#[test]
+fn find_and_filter_glitches() -> PolarsResult<()> {
+    let df = glitchy_log()?;
+    let checker = QualityChecker::default();
+    let report = checker.check(&df);
+
+    let kinds = report
+        .issues
+        .iter()
+        .map(|i| (i.column.as_str(), i.kind, i.start, i.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("E1 OilT", IssueKind::Stuck, 0, 300),
+            ("Latitude/Longitude", IssueKind::PositionJump, 50, 51),
+            ("IAS", IssueKind::Spike, 100, 101),
+            ("E1 CHT1", IssueKind::Frozen, 150, 230),
+        ]
+    );
+
+    // the spike and jump are interpolated from the rows either side
+    let despiked = checker.despike(&df)?;
+    let ias = column_f64(&despiked, "IAS")[100].unwrap();
+    assert!((ias - (100.0 + 5.0 * 10f64.sin())).abs() < 0.1);
+    let latitude = column_f64(&despiked, "Latitude")[50].unwrap();
+    assert!((latitude - (41.6 + 50.0 * 0.0005)).abs() < 1e-9);
+    assert_eq!(column_f64(&despiked, "E1 CHT1"), column_f64(&df, "E1 CHT1"));
+    assert!(checker
+        .check(&despiked)
+        .issues
+        .iter()
+        .all(|i| i.kind != IssueKind::Spike));
+
+    // a moving median removes the spike too
+    let filtered = median_filter(&df, 5)?;
+    assert!(column_f64(&filtered, "IAS")[100].unwrap() < 110.0);
+    Ok(())
+}
+
+#[test]
+fn check_sample_log() {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read().unwrap();
+    let report = QualityChecker::default().check(&log.data);
+    assert_eq!(report.rows, 3676);
+    assert_eq!(report.count(IssueKind::PositionJump), 0);
+    assert_eq!(report.count(IssueKind::Frozen), 0);
+    assert_eq!(report.count(IssueKind::Stuck), 0);
+    assert!(report.count(IssueKind::Spike) < 20);
+}
@@ -0,0 +1,89 @@
+use chrono::{NaiveDate, TimeDelta};
+use hangar::avionics::AvionicsLogSource;
+use hangar::data::{column_f64, elapsed_seconds};
+use hangar::resample::Resampler;
+use hangar::resource_path;
+use polars::prelude::*;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+/// Rows at 0, 1, 1 (a duplicate), 2 and 10 seconds, turning through north and rolling through wings level
+fn jittery_log() -> PolarsResult<DataFrame> {
+    let start = NaiveDate::from_ymd_opt(2023, 11, 4)
+        .unwrap()
+        .and_hms_opt(14, 0, 0)
+        .unwrap();
+    let seconds = [0, 1, 1, 2, 10];
+    DataFrame::new(vec![
+        Column::new(
+            "Timestamp".into(),
+            seconds
+                .iter()
+                .map(|&s| start + TimeDelta::seconds(s))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("AltB".into(), [1000.0, 5000.0, 1010.0, 1020.0, 1100.0]),
+        Column::new("HDG".into(), [358.0, 90.0, 2.0, 6.0, 10.0]),
+        Column::new("Roll".into(), [-170.0, 0.0, 170.0, 10.0, 0.0]),
+        Column::new("GPSfix".into(), [3i64, 3, 3, 4, 4]),
+        Column::new("AtvWpt".into(), ["KPOU", "KPOU", "KPOU", "KHPN", "KHPN"]),
+    ])
+}
+
+#[test]
+fn resample_jittery_log() -> PolarsResult<()> {
+    let df = Resampler::new(2.0).unwrap().resample(&jittery_log()?)?;
+    assert_eq!(df.height(), 21);
+    assert_eq!(elapsed_seconds(&df)[3], Some(1.5));
+
+    // linear, keeping the last of the duplicate rows
+    let altitude = column_f64(&df, "AltB");
+    assert_eq!(altitude[2], Some(1010.0));
+    assert_eq!(altitude[3], Some(1015.0));
+
+    // angles along the shortest arc
+    let heading = column_f64(&df, "HDG");
+    assert_eq!(heading[1], Some(0.0));
+    let roll = column_f64(&df, "Roll");
+    assert_eq!(roll[1], Some(-180.0));
+
+    // enumerations and text hold their values
+    assert_eq!(column_f64(&df, "GPSfix")[3], Some(3.0));
+    assert_eq!(df.column("AtvWpt")?.str()?.get(5), Some("KHPN"));
+
+    // the 8 second gap is longer than the max gap, so only the rows at its ends are known
+    assert_eq!(altitude[6], None);
+    assert_eq!(heading[20], Some(10.0));
+    assert_eq!(df.column("AtvWpt")?.str()?.get(19), None);
+
+    let bridged = Resampler {
+        rate: 2.0,
+        max_gap: 10.0,
+    };
+    assert_eq!(
+        column_f64(&bridged.resample(&jittery_log()?)?, "AltB")[12],
+        Some(1060.0)
+    );
+    Ok(())
+}
+
+#[test]
+fn resample_sample_log() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let df = Resampler::new(5.0)?.resample(&log.data).map_err(|e| e.to_string())?;
+
+    let elapsed = elapsed_seconds(&df);
+    let original = elapsed_seconds(&log.data);
+    assert_eq!(elapsed.last(), original.last());
+    assert!(elapsed
+        .windows(2)
+        .all(|t| (t[1].unwrap() - t[0].unwrap() - 0.2).abs() < 1e-9));
+    assert_eq!(df.width(), log.data.width());
+
+    // rates that are not positive or are above the cap are rejected
+    for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1000.0] {
+        assert!(Resampler::new(rate).is_err(), "{}", rate);
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use chrono::{TimeZone, Utc};
+use hangar::analysis::summary::FlightSummary;
+use hangar::avionics::AvionicsLogSource;
+use hangar::resource_path;
+
+// A Garmin EIS file for a Mooney M20J
+const SAMPLE_CSV: &str = "log_231104_084813_KPOU.csv";
+
+#[test]
+fn summarize_sample_flight() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let summary = FlightSummary::from_log(&log);
+
+    assert_eq!(summary.tail_number.as_deref(), Some("N12345"));
+    assert_eq!(
+        summary.off_time,
+        Some(Utc.with_ymd_and_hms(2023, 11, 4, 13, 2, 28).unwrap())
+    );
+    assert_eq!(
+        summary.on_time,
+        Some(Utc.with_ymd_and_hms(2023, 11, 4, 13, 45, 10).unwrap())
+    );
+    assert!(summary.out_time < summary.off_time);
+    assert!(summary.in_time > summary.on_time);
+    assert!((summary.flight_hours.unwrap() - 0.71).abs() < 0.01);
+
+    // departing Poughkeepsie (KPOU)
+    let departure = summary.departure.unwrap();
+    assert!((departure.latitude - 41.63).abs() < 0.01 && (departure.longitude + 73.88).abs() < 0.01);
+    assert_eq!(summary.departure_airport.as_deref(), Some("KPOU"));
+    assert!(summary.distance_nm > 80.0 && summary.distance_nm < 100.0);
+    assert!(summary.max_altitude.unwrap() > 5000.0);
+
+    // the load factor stays near 1 G in smooth air
+    assert!(summary.min_normal_g.unwrap() > 0.7 && summary.max_normal_g.unwrap() < 1.3);
+    assert!(summary.fuel_used_quantity.unwrap() > 0.0);
+    assert!(summary.fuel_used_flow.unwrap() > 0.0);
+    assert_eq!(summary.engine_peaks["E1 RPM"].round(), 2696.0);
+    Ok(())
+}
+
+#[test]
+fn summary_json() -> Result<(), String> {
+    let log = AvionicsLogSource::Garmin(resource_path(SAMPLE_CSV)).read()?;
+    let summary = FlightSummary::from_log(&log);
+
+    let json: serde_json::Value = serde_json::to_value(&summary).map_err(|e| e.to_string())?;
+    assert_eq!(json["off_time"], "2023-11-04T13:02:28Z");
+    assert_eq!(json["tail_number"], "N12345");
+    assert!(json["engine_peaks"]["E1 CHT4"].is_number());
+
+    // the text report has a line per statistic
+    let text = summary.to_string();
+    assert!(text.contains("Off                2023-11-04 13:02:28 UTC"));
+    Ok(())
+}